@@ -0,0 +1,54 @@
+// aura ring LED settings (R503 and similar)
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuraMode {
+    Breathing = 0x01,
+    Flashing = 0x02,
+    AlwaysOn = 0x03,
+    AlwaysOff = 0x04,
+    GraduallyOn = 0x05,
+    GraduallyOff = 0x06,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuraColour {
+    Red = 0x01,
+    Blue = 0x02,
+    Purple = 0x03,
+    Green = 0x04,
+    Yellow = 0x05,
+    Cyan = 0x06,
+    White = 0x07,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuraLedConfig {
+    pub mode: AuraMode,
+    // lower is faster, only used by breathing, flashing and gradual modes
+    pub speed: u8,
+    pub colour: AuraColour,
+    // number of cycles, 0 loops forever
+    pub count: u8,
+}
+
+impl AuraLedConfig {
+    pub fn breathing(colour: AuraColour) -> Self {
+        AuraLedConfig { mode: AuraMode::Breathing, speed: 0x80, colour, count: 0 }
+    }
+
+    pub fn flashing(colour: AuraColour, count: u8) -> Self {
+        AuraLedConfig { mode: AuraMode::Flashing, speed: 0x20, colour, count }
+    }
+
+    pub fn on(colour: AuraColour) -> Self {
+        AuraLedConfig { mode: AuraMode::AlwaysOn, speed: 0, colour, count: 0 }
+    }
+
+    pub fn off() -> Self {
+        AuraLedConfig { mode: AuraMode::AlwaysOff, speed: 0, colour: AuraColour::Red, count: 0 }
+    }
+
+    pub(crate) fn to_payload(self) -> [u8; 4] {
+        [self.mode as u8, self.speed, self.colour as u8, self.count]
+    }
+}
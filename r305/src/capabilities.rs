@@ -0,0 +1,270 @@
+use crate::protocol::{ProductInfo, SystemParameters};
+
+use alloc::format;
+use alloc::string::{String, ToString};
+
+// system identifier code reported by the original ZFM based modules (R305, R307)
+const ZFM_SYSTEM_ID: u16 = 0x0009;
+
+// module types whose manuals document the aura LED and the AutoEnroll/AutoIdentify commands,
+// everything else with ReadProdInfo gets the manual enroll and search flows
+const AURA_AUTO_MODELS: [&str; 2] = ["R502", "R503"];
+
+// what the connected module can do beyond the basic R305 command set
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub model: String,
+    pub product_info: bool,
+    pub aura_led: bool,
+    pub auto_enroll: bool,
+    pub auto_identify: bool,
+    pub high_speed_search: bool,
+}
+
+impl Capabilities {
+    pub fn detect(params: &SystemParameters, product_info: Option<&ProductInfo>) -> Self {
+        match product_info {
+            Some(info) => {
+                let model = info.module_type.to_uppercase();
+                let documented = AURA_AUTO_MODELS.iter().any(|family| model.starts_with(family));
+
+                Capabilities {
+                    model: info.module_type.clone(),
+                    product_info: true,
+                    aura_led: documented,
+                    auto_enroll: documented,
+                    auto_identify: documented,
+                    // not every module that answers ReadProdInfo has it, the driver probes for it
+                    high_speed_search: false,
+                }
+            }
+            None => {
+                // HighSpeedSearch is part of the ZFM command set, unknown modules get the basics
+                let zfm = params.system_id == ZFM_SYSTEM_ID;
+                let model = if zfm {
+                    "R305/R307".to_string()
+                } else {
+                    format!("Unknown (system id 0x{:04X})", params.system_id)
+                };
+
+                Capabilities { model, high_speed_search: zfm, ..Default::default() }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::{FingerprintDriver, SearchMode};
+    use crate::error::Error;
+    use crate::protocol::*;
+
+    use alloc::collections::VecDeque;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use embedded_io::{ErrorKind, ErrorType, Read, Write};
+
+    const ADDRESS: u32 = 0xFFFFFFFF;
+    const ZFM: u16 = 0x0009;
+
+    // a module that answers each command it expects with a scripted ack payload, or stays
+    // quiet for None so the read times out like a real port
+    struct MockPort {
+        script: VecDeque<(Command, Option<Vec<u8>>)>,
+        written: Vec<u8>,
+        pending: VecDeque<u8>,
+    }
+
+    impl MockPort {
+        fn new(script: Vec<(Command, Option<Vec<u8>>)>) -> Self {
+            MockPort { script: script.into(), written: Vec::new(), pending: VecDeque::new() }
+        }
+    }
+
+    impl ErrorType for MockPort {
+        type Error = ErrorKind;
+    }
+
+    impl Read for MockPort {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if self.pending.is_empty() {
+                return Err(ErrorKind::TimedOut);
+            }
+            let n = buf.len().min(self.pending.len());
+            for (slot, byte) in buf.iter_mut().zip(self.pending.drain(..n)) {
+                *slot = byte;
+            }
+            Ok(n)
+        }
+    }
+
+    impl Write for MockPort {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.written.extend_from_slice(buf);
+            if self.written.len() >= 9 {
+                let length = 9 + u16::from_be_bytes([self.written[7], self.written[8]]) as usize;
+                if self.written.len() >= length {
+                    let packet: Vec<u8> = self.written.drain(..length).collect();
+                    let (expected, reply) = self.script.pop_front().expect("a command after the script ended");
+                    assert_eq!(packet[9], expected.code(), "expected {}", expected.name());
+                    if let Some(payload) = reply {
+                        self.pending.extend(encode_packet(ADDRESS, ACK_PACKET, &payload));
+                    }
+                }
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    fn ok() -> Option<Vec<u8>> {
+        Some(vec![FINGERPRINT_OK])
+    }
+
+    fn code(code: u8) -> Option<Vec<u8>> {
+        Some(vec![code])
+    }
+
+    fn system_parameters(system_id: u16, capacity: u16) -> Option<Vec<u8>> {
+        let mut payload = vec![FINGERPRINT_OK, 0x00, 0x00];
+        payload.extend_from_slice(&system_id.to_be_bytes());
+        payload.extend_from_slice(&capacity.to_be_bytes());
+        // security level 3, default address, 128 byte packets, 57600 baud
+        payload.extend_from_slice(&[0x00, 0x03, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x02, 0x00, 0x06]);
+        Some(payload)
+    }
+
+    fn product_info(module_type: &str) -> Option<Vec<u8>> {
+        let mut payload = vec![FINGERPRINT_OK];
+        let mut padded = |text: &str, len: usize| {
+            payload.extend(text.bytes().chain(core::iter::repeat(0)).take(len));
+        };
+        padded(module_type, 16);
+        padded("0001", 4);
+        padded("12345678", 8);
+        padded("", 2);
+        padded("FPC1011F", 8);
+        payload.extend_from_slice(&[0x00, 0xA0, 0x00, 0xA0, 0x06, 0x00, 0x00, 0xC8]);
+        Some(payload)
+    }
+
+    fn connect(script: Vec<(Command, Option<Vec<u8>>)>) -> Result<FingerprintDriver<MockPort>, Error<ErrorKind>> {
+        FingerprintDriver::new(MockPort::new(script), ADDRESS, 0)
+    }
+
+    fn info(module_type: &str) -> ProductInfo {
+        ProductInfo {
+            module_type: module_type.to_string(),
+            batch_number: String::new(),
+            serial_number: String::new(),
+            hardware_version: (1, 0),
+            sensor_type: String::new(),
+            sensor_width: 160,
+            sensor_height: 160,
+            template_size: 1536,
+            database_size: 200,
+        }
+    }
+
+    #[test]
+    fn zfm_system_id_without_product_info_is_an_r305() {
+        let params = SystemParameters { system_id: ZFM, ..Default::default() };
+        let capabilities = Capabilities::detect(&params, None);
+
+        assert_eq!(capabilities.model, "R305/R307");
+        assert!(capabilities.high_speed_search);
+        assert!(!capabilities.product_info && !capabilities.aura_led && !capabilities.auto_enroll);
+    }
+
+    #[test]
+    fn unknown_system_id_gets_only_the_basics() {
+        let params = SystemParameters { system_id: 0x1234, ..Default::default() };
+        let capabilities = Capabilities::detect(&params, None);
+
+        assert_eq!(capabilities.model, "Unknown (system id 0x1234)");
+        assert_eq!(capabilities, Capabilities { model: capabilities.model.clone(), ..Default::default() });
+    }
+
+    #[test]
+    fn product_info_names_the_model_whatever_the_system_id() {
+        let params = SystemParameters { system_id: ZFM, ..Default::default() };
+
+        let r503 = Capabilities::detect(&params, Some(&info("r503-mini")));
+        assert_eq!(r503.model, "r503-mini");
+        assert!(r503.product_info && r503.aura_led && r503.auto_enroll && r503.auto_identify);
+        // left to the driver's probe
+        assert!(!r503.high_speed_search);
+
+        let other = Capabilities::detect(&params, Some(&info("FPM383C")));
+        assert!(other.product_info);
+        assert!(!other.aura_led && !other.auto_enroll && !other.auto_identify && !other.high_speed_search);
+    }
+
+    #[test]
+    fn module_that_never_answers_read_prod_info_falls_back_to_the_system_id() {
+        let driver = connect(vec![
+            (Command::VerifyPassword(0), ok()),
+            (Command::GetSystemParameters, system_parameters(ZFM, 1000)),
+            (Command::ReadProductInfo, None),
+        ])
+        .unwrap();
+
+        assert_eq!(driver.capabilities().model, "R305/R307");
+        assert!(driver.capabilities().high_speed_search);
+        assert_eq!(driver.search_mode(), SearchMode::HighSpeed);
+        assert_eq!(driver.parameters().storage_capacity, 1000);
+    }
+
+    #[test]
+    fn module_that_rejects_read_prod_info_falls_back_to_the_system_id() {
+        let driver = connect(vec![
+            (Command::VerifyPassword(0), ok()),
+            (Command::GetSystemParameters, system_parameters(0x0000, 300)),
+            (Command::ReadProductInfo, code(FINGERPRINT_ERROR_COMMUNICATION)),
+        ])
+        .unwrap();
+
+        assert_eq!(driver.capabilities().model, "Unknown (system id 0x0000)");
+        assert!(!driver.capabilities().product_info && !driver.capabilities().high_speed_search);
+        assert_eq!(driver.search_mode(), SearchMode::Normal);
+    }
+
+    #[test]
+    fn read_prod_info_path_probes_high_speed_search() {
+        let probe = Command::HighSpeedSearch { buffer: CharBuffer::One, start: 0, count: 1 };
+
+        let r503 = connect(vec![
+            (Command::VerifyPassword(0), ok()),
+            (Command::GetSystemParameters, system_parameters(ZFM, 200)),
+            (Command::ReadProductInfo, product_info("R503")),
+            (probe.clone(), code(FINGERPRINT_ERROR_NO_TEMPLATE_FOUND)),
+        ])
+        .unwrap();
+        assert_eq!(r503.capabilities().model, "R503");
+        assert!(r503.capabilities().aura_led && r503.capabilities().auto_enroll);
+        assert!(r503.capabilities().high_speed_search);
+        assert_eq!(r503.search_mode(), SearchMode::HighSpeed);
+
+        // answers ReadProdInfo but not the probe
+        let other = connect(vec![
+            (Command::VerifyPassword(0), ok()),
+            (Command::GetSystemParameters, system_parameters(ZFM, 200)),
+            (Command::ReadProductInfo, product_info("FPM383C")),
+            (probe, code(FINGERPRINT_ERROR_COMMUNICATION)),
+        ])
+        .unwrap();
+        assert_eq!(other.capabilities().model, "FPM383C");
+        assert!(!other.capabilities().aura_led && !other.capabilities().high_speed_search);
+        assert_eq!(other.search_mode(), SearchMode::Normal);
+    }
+
+    #[test]
+    fn wrong_password_stops_before_probing() {
+        let result = connect(vec![(Command::VerifyPassword(0), code(FINGERPRINT_ERROR_WRONG_PASSWORD))]);
+        assert!(matches!(result, Err(Error::Auth)));
+    }
+}
@@ -0,0 +1,459 @@
+use crate::aura::AuraLedConfig;
+use crate::capabilities::Capabilities;
+use crate::error::Error;
+use crate::protocol::*;
+
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use embedded_hal::delay::DelayNs;
+use embedded_io::{Error as _, ErrorKind, Read, Write};
+
+// templates per ReadIndexTable page
+pub const INDEX_PAGE_SIZE: u16 = 256;
+
+// GenImg attempts while no finger is on the sensor, and the pause between them
+const CAPTURE_RETRIES: u32 = 10;
+const CAPTURE_RETRY_DELAY_MS: u32 = 500;
+
+// which instruction searches the library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    Normal,
+    HighSpeed,
+}
+
+// command level driver, owns the serial port and knows what the module supports
+pub struct FingerprintDriver<P> {
+    port: P,
+    address: u32,
+    password: u32,
+    capabilities: Capabilities,
+    parameters: SystemParameters,
+    search_mode: SearchMode,
+}
+
+impl<P: Read + Write> FingerprintDriver<P> {
+    // verifies the password and probes the module before handing out the driver
+    pub fn new(port: P, address: u32, password: u32) -> Result<Self, Error<P::Error>> {
+        let mut driver = FingerprintDriver {
+            port,
+            address,
+            password,
+            capabilities: Capabilities::default(),
+            parameters: SystemParameters::default(),
+            search_mode: SearchMode::Normal,
+        };
+
+        if !matches!(driver.verify_password(), Ok(true)) {
+            return Err(Error::Auth);
+        }
+
+        driver.detect_capabilities()?;
+        Ok(driver)
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    // system parameters read when the driver was created
+    pub fn parameters(&self) -> &SystemParameters {
+        &self.parameters
+    }
+
+    pub fn search_mode(&self) -> SearchMode {
+        self.search_mode
+    }
+
+    // picks the search instruction, HighSpeed is already the default where supported
+    pub fn set_search_mode(&mut self, mode: SearchMode) -> Result<(), Error<P::Error>> {
+        if mode == SearchMode::HighSpeed && !self.capabilities.high_speed_search {
+            return Err(self.unsupported("HighSpeedSearch"));
+        }
+        self.search_mode = mode;
+        Ok(())
+    }
+
+    // sends any command and decodes its acknowledgement, for flows the helpers don't cover
+    pub fn execute(&mut self, command: Command) -> Result<Response, Error<P::Error>> {
+        if !self.supports(&command) {
+            return Err(self.unsupported(command.name()));
+        }
+        self.transact(&command)
+    }
+
+    // reads product info, fails with Unsupported on modules without ReadProdInfo
+    pub fn get_product_info(&mut self) -> Result<ProductInfo, Error<P::Error>> {
+        match self.execute(Command::ReadProductInfo)? {
+            Response::ProductInfo(info) => Ok(info),
+            other => Err(Self::unexpected(&Command::ReadProductInfo, &other)),
+        }
+    }
+
+    // sets the aura ring LED, fails with Unsupported on modules without one
+    pub fn set_aura_led(&mut self, config: AuraLedConfig) -> Result<(), Error<P::Error>> {
+        self.execute(Command::AuraLedConfig(config))?;
+        Ok(())
+    }
+
+    pub fn get_system_parameters(&mut self) -> Result<SystemParameters, Error<P::Error>> {
+        match self.transact(&Command::GetSystemParameters)? {
+            Response::SystemParameters(params) => Ok(params),
+            other => Err(Self::unexpected(&Command::GetSystemParameters, &other)),
+        }
+    }
+
+    // drops all stored templates
+    pub fn delete_all(&mut self) -> Result<(), Error<P::Error>> {
+        self.transact(&Command::ClearDatabase)?;
+        Ok(())
+    }
+
+    // captures a finger into the image buffer, retrying while no finger is on the sensor
+    pub fn capture_image(&mut self, delay: &mut impl DelayNs) -> Result<(), Error<P::Error>> {
+        for _ in 0..CAPTURE_RETRIES {
+            match self.transact(&Command::ReadImage) {
+                Ok(_) => return Ok(()),
+                Err(Error::SensorError(FINGERPRINT_ERROR_NO_FINGER)) => {
+                    delay.delay_ms(CAPTURE_RETRY_DELAY_MS);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(Error::MaxRetries)
+    }
+
+    // generates features from the image buffer into a char buffer
+    pub fn generate_char(&mut self, buffer: CharBuffer) -> Result<(), Error<P::Error>> {
+        self.transact(&Command::ConvertImage { buffer })?;
+        Ok(())
+    }
+
+    // merges both char buffers into one template, left in both buffers
+    pub fn merge_chars(&mut self) -> Result<(), Error<P::Error>> {
+        self.transact(&Command::CreateTemplate)?;
+        Ok(())
+    }
+
+    // stores a char buffer into a library slot
+    pub fn store_char(&mut self, buffer: CharBuffer, id: u16) -> Result<(), Error<P::Error>> {
+        self.transact(&Command::StoreTemplate { buffer, id })?;
+        Ok(())
+    }
+
+    // loads a stored template from a library slot into a char buffer
+    pub fn load_char(&mut self, buffer: CharBuffer, id: u16) -> Result<(), Error<P::Error>> {
+        self.transact(&Command::LoadTemplate { buffer, id })?;
+        Ok(())
+    }
+
+    // compares CharBuffer1 against CharBuffer2, the score if they match
+    pub fn match_chars(&mut self) -> Result<Option<u16>, Error<P::Error>> {
+        match self.transact(&Command::Match)? {
+            Response::Score(score) => Ok(Some(score)),
+            Response::NoMatch => Ok(None),
+            other => Err(Self::unexpected(&Command::Match, &other)),
+        }
+    }
+
+    // searches a range of library slots for the template in a char buffer, (slot, score) on a hit
+    pub fn search_char(
+        &mut self,
+        buffer: CharBuffer,
+        start: u16,
+        count: u16,
+    ) -> Result<Option<(u16, u16)>, Error<P::Error>> {
+        let command = match self.search_mode {
+            SearchMode::Normal => Command::Search { buffer, start, count },
+            SearchMode::HighSpeed => Command::HighSpeedSearch { buffer, start, count },
+        };
+        match self.transact(&command)? {
+            Response::Match { id, score } => Ok(Some((id, score))),
+            Response::NoMatch => Ok(None),
+            other => Err(Self::unexpected(&command, &other)),
+        }
+    }
+
+    // reads a char buffer out of the module as raw template bytes
+    pub fn upload_char(&mut self, buffer: CharBuffer) -> Result<Vec<u8>, Error<P::Error>> {
+        self.transact(&Command::UploadTemplate { buffer })?;
+
+        let mut data = Vec::new();
+        loop {
+            let raw = self.receive_packet("UpChar")?;
+            let (packet_type, payload) = decode_packet(self.address, &raw, "UpChar")?;
+            data.extend_from_slice(payload);
+
+            match packet_type {
+                DATA_PACKET => continue,
+                END_DATA_PACKET => return Ok(data),
+                packet_type => {
+                    return Err(Error::UnexpectedPacketType {
+                        command: "UpChar",
+                        packet_type,
+                        received: raw,
+                    });
+                }
+            }
+        }
+    }
+
+    // writes raw template bytes into a char buffer
+    pub fn download_char(&mut self, buffer: CharBuffer, data: &[u8]) -> Result<(), Error<P::Error>> {
+        if data.is_empty() {
+            return Err(Error::Protocol("Template data is empty".to_string()));
+        }
+        self.transact(&Command::DownloadTemplate { buffer })?;
+
+        let chunks: Vec<&[u8]> = data.chunks(self.data_packet_size()).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let packet_type = if i + 1 == chunks.len() { END_DATA_PACKET } else { DATA_PACKET };
+            let packet = encode_packet(self.address, packet_type, chunk);
+            self.send_packet(&packet, "DownChar")?;
+        }
+        Ok(())
+    }
+
+    // raw bytes of the template stored in a library slot
+    pub fn upload_template(&mut self, id: u16) -> Result<Vec<u8>, Error<P::Error>> {
+        self.load_char(CharBuffer::One, id)?;
+        self.upload_char(CharBuffer::One)
+    }
+
+    // stores raw template bytes into a library slot
+    pub fn download_template(&mut self, id: u16, data: &[u8]) -> Result<(), Error<P::Error>> {
+        self.download_char(CharBuffer::One, data)?;
+        self.store_char(CharBuffer::One, id)
+    }
+
+    pub fn template_count(&mut self) -> Result<u16, Error<P::Error>> {
+        match self.transact(&Command::TemplateCount)? {
+            Response::TemplateCount(count) => Ok(count),
+            other => Err(Self::unexpected(&Command::TemplateCount, &other)),
+        }
+    }
+
+    // every library slot that holds a template, walking the index table pages
+    pub fn occupied_slots(&mut self) -> Result<Vec<u16>, Error<P::Error>> {
+        let capacity = self.parameters.storage_capacity;
+        let mut slots = Vec::new();
+
+        for page in 0..capacity.div_ceil(INDEX_PAGE_SIZE) {
+            let command = Command::ReadIndexTable { page: page as u8 };
+            let table = match self.transact(&command)? {
+                Response::IndexTable(table) => table,
+                other => return Err(Self::unexpected(&command, &other)),
+            };
+
+            for (byte_idx, byte) in table.iter().enumerate() {
+                for bit in 0..8 {
+                    let id = page * INDEX_PAGE_SIZE + (byte_idx * 8 + bit) as u16;
+                    if byte & (1 << bit) != 0 && id < capacity {
+                        slots.push(id);
+                    }
+                }
+            }
+        }
+
+        Ok(slots)
+    }
+
+    // the module runs the whole enroll sequence, see transact_with for the callbacks
+    pub fn auto_enroll(
+        &mut self,
+        id: u16,
+        keep_waiting: impl FnMut() -> bool,
+        on_step: impl FnMut(u8, u8),
+    ) -> Result<(), Error<P::Error>> {
+        let command = Command::AutoEnroll {
+            id,
+            captures: AUTO_ENROLL_CAPTURES,
+            parameter: AUTO_ENROLL_ALLOW_OVERWRITE,
+        };
+        if !self.supports(&command) {
+            return Err(self.unsupported(command.name()));
+        }
+
+        self.transact_with(&command, keep_waiting, on_step)?;
+        Ok(())
+    }
+
+    // the module captures and searches the whole library in one command
+    pub fn auto_identify(
+        &mut self,
+        keep_waiting: impl FnMut() -> bool,
+        on_step: impl FnMut(u8, u8),
+    ) -> Result<Option<u16>, Error<P::Error>> {
+        let command = Command::AutoIdentify {
+            security_level: self.parameters.security_level as u8,
+            id: AUTO_IDENTIFY_WHOLE_LIBRARY,
+            captures: AUTO_IDENTIFY_CAPTURES,
+            parameter: 0x0000,
+        };
+        if !self.supports(&command) {
+            return Err(self.unsupported(command.name()));
+        }
+
+        match self.transact_with(&command, keep_waiting, on_step)? {
+            Response::Match { id, .. } => Ok(Some(id)),
+            Response::NoMatch => Ok(None),
+            other => Err(Self::unexpected(&command, &other)),
+        }
+    }
+
+    // sends a command and reads acks until the final one, auto commands report steps first.
+    // keep_waiting is asked after each port timeout with nothing received, auto commands
+    // only answer once a finger is placed so those timeouts are not failures
+    pub fn transact_with(
+        &mut self,
+        command: &Command,
+        mut keep_waiting: impl FnMut() -> bool,
+        mut on_step: impl FnMut(u8, u8),
+    ) -> Result<Response, Error<P::Error>> {
+        let name = command.name();
+        let packet = encode_packet(self.address, COMMAND_PACKET, &command.encode());
+        self.send_packet(&packet, name)?;
+
+        loop {
+            let raw = match self.receive_packet(name) {
+                Err(Error::Io { error, received, .. })
+                    if received.is_empty() && error.kind() == ErrorKind::TimedOut && keep_waiting() =>
+                {
+                    continue
+                }
+                result => result?,
+            };
+            let (packet_type, payload) = decode_packet(self.address, &raw, name)?;
+
+            if packet_type != ACK_PACKET {
+                return Err(Error::UnexpectedPacketType { command: name, packet_type, received: raw });
+            }
+
+            match Response::decode(command, payload)? {
+                Response::AutoStep { step, detail } => on_step(step, detail),
+                response => return Ok(response),
+            }
+        }
+    }
+
+    // probes the module once so callers can check what it supports
+    fn detect_capabilities(&mut self) -> Result<(), Error<P::Error>> {
+        let params = self.get_system_parameters()?;
+
+        // older modules either reject ReadProdInfo or never answer it
+        let product_info = match self.transact(&Command::ReadProductInfo) {
+            Ok(Response::ProductInfo(info)) => Some(info),
+            _ => None,
+        };
+
+        let has_product_info = product_info.is_some();
+        self.capabilities = Capabilities::detect(&params, product_info.as_ref());
+        self.parameters = params;
+        if has_product_info {
+            self.capabilities.high_speed_search = self.probe_high_speed_search();
+        }
+        if self.capabilities.high_speed_search {
+            self.search_mode = SearchMode::HighSpeed;
+        }
+        Ok(())
+    }
+
+    // searches one slot for whatever is in CharBuffer1, a module with the instruction answers
+    // with a match or no match, anything else falls back to the normal search
+    fn probe_high_speed_search(&mut self) -> bool {
+        let probe = Command::HighSpeedSearch { buffer: CharBuffer::One, start: 0, count: 1 };
+        matches!(self.transact(&probe), Ok(Response::Match { .. } | Response::NoMatch))
+    }
+
+    // data packet payload size from the packet length code in the system parameters
+    fn data_packet_size(&self) -> usize {
+        32 << self.parameters.packet_length.min(3)
+    }
+
+    // whether the detected module understands the command at all
+    fn supports(&self, command: &Command) -> bool {
+        match command {
+            Command::ReadProductInfo => self.capabilities.product_info,
+            Command::AuraLedConfig(_) => self.capabilities.aura_led,
+            Command::AutoEnroll { .. } => self.capabilities.auto_enroll,
+            Command::AutoIdentify { .. } => self.capabilities.auto_identify,
+            Command::HighSpeedSearch { .. } => self.capabilities.high_speed_search,
+            _ => true,
+        }
+    }
+
+    fn unsupported(&self, command: &'static str) -> Error<P::Error> {
+        Error::Unsupported { command, model: self.capabilities.model.clone() }
+    }
+
+    fn unexpected(command: &Command, response: &Response) -> Error<P::Error> {
+        Error::Protocol(format!("Unexpected response to {}: {:?}", command.name(), response))
+    }
+
+    //verifies password
+    fn verify_password(&mut self) -> Result<bool, Error<P::Error>> {
+        match self.transact(&Command::VerifyPassword(self.password)) {
+            Ok(_) => Ok(true),
+            Err(Error::SensorError(FINGERPRINT_ERROR_WRONG_PASSWORD)) => Ok(false),
+            Err(Error::SensorError(FINGERPRINT_ERROR_COMMUNICATION)) => {
+                Err(Error::Protocol("Communication failed".to_string()))
+            }
+            Err(Error::SensorError(FINGERPRINT_ADDR_CODE)) => Err(Error::Auth),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn transact(&mut self, command: &Command) -> Result<Response, Error<P::Error>> {
+        self.transact_with(command, || false, |_, _| {})
+    }
+
+    //helper to send packet to sensor
+    fn send_packet(&mut self, packet: &[u8], command: &'static str) -> Result<(), Error<P::Error>> {
+        self.port
+            .write_all(packet)
+            .and_then(|_| self.port.flush())
+            .map_err(|error| Error::Io { command, error, received: Vec::new() })
+    }
+
+    // reads one whole packet, header first for the length
+    fn receive_packet(&mut self, command: &'static str) -> Result<Vec<u8>, Error<P::Error>> {
+        let mut header = [0u8; 9];
+        self.read_fully(&mut header, command, &[])?;
+
+        let length = u16::from_be_bytes([header[7], header[8]]) as usize;
+        let mut body = vec![0u8; length];
+        self.read_fully(&mut body, command, &header)?;
+
+        let mut full = header.to_vec();
+        full.extend(body);
+        Ok(full)
+    }
+
+    // read_exact that keeps whatever arrived before a failure for the error
+    fn read_fully(
+        &mut self,
+        buffer: &mut [u8],
+        command: &'static str,
+        before: &[u8],
+    ) -> Result<(), Error<P::Error>> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match self.port.read(&mut buffer[filled..]) {
+                Ok(0) => {
+                    let received = [before, &buffer[..filled]].concat();
+                    return Err(Error::Closed { command, received });
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    let received = [before, &buffer[..filled]].concat();
+                    return Err(Error::Io { command, error, received });
+                }
+            }
+        }
+        Ok(())
+    }
+}
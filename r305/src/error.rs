@@ -0,0 +1,40 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use thiserror::Error;
+
+// driver errors, E is the serial port's own error type
+#[derive(Error, Debug)]
+pub enum Error<E> {
+    #[error("Serial error during {command}: {error:?} (got {received:02X?})")]
+    Io { command: &'static str, error: E, received: Vec<u8> },
+
+    #[error("Serial port closed during {command} (got {received:02X?})")]
+    Closed { command: &'static str, received: Vec<u8> },
+
+    #[error("Checksum mismatch in reply to {command}: expected 0x{expected:04X}, got 0x{found:04X} in {received:02X?}")]
+    ChecksumMismatch { command: &'static str, expected: u16, found: u16, received: Vec<u8> },
+
+    #[error("Unexpected packet type 0x{packet_type:02X} in reply to {command}: {received:02X?}")]
+    UnexpectedPacketType { command: &'static str, packet_type: u8, received: Vec<u8> },
+
+    #[error("Malformed reply to {command}, {reason}: {received:02X?}")]
+    Malformed { command: &'static str, reason: String, received: Vec<u8> },
+
+    #[error("Reply to {command} came from address 0x{found:08X}, expected 0x{expected:08X}")]
+    AddressMismatch { command: &'static str, expected: u32, found: u32 },
+
+    #[error("Invalid password or communication error")]
+    Auth,
+
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+
+    #[error("Sensor returned error code: 0x{0:02X}")]
+    SensorError(u8),
+
+    #[error("{command} is not supported by this sensor ({model})")]
+    Unsupported { command: &'static str, model: String },
+
+    #[error("Reached Maximum Retry limit")]
+    MaxRetries,
+}
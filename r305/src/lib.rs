@@ -0,0 +1,14 @@
+// R305/R503 fingerprint module driver without std, needs an allocator.
+// runs over any embedded-io serial port, e.g. an RP2040 UART or a std adapter
+#![no_std]
+
+extern crate alloc;
+
+pub mod aura;
+pub mod capabilities;
+pub mod driver;
+pub mod error;
+pub mod protocol;
+
+pub use driver::{FingerprintDriver, SearchMode};
+pub use error::Error;
@@ -0,0 +1,562 @@
+use crate::aura::AuraLedConfig;
+use crate::error::Error;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub const PACKET_START_CODE_1: u8 = 0xEF;
+pub const PACKET_START_CODE_2: u8 = 0x01;
+
+//packet types
+pub const COMMAND_PACKET: u8 = 0x01;
+pub const DATA_PACKET: u8 = 0x02;
+pub const ACK_PACKET: u8 = 0x07;
+pub const END_DATA_PACKET: u8 = 0x08;
+
+//acknowledgment Codes (Payload[0] of ACK_PACKET)
+pub const FINGERPRINT_OK: u8 = 0x00;
+pub const FINGERPRINT_ERROR_COMMUNICATION: u8 = 0x01;
+pub const FINGERPRINT_ERROR_NO_FINGER: u8 = 0x02;
+pub const FINGERPRINT_ERROR_WRONG_PASSWORD: u8 = 0x02;
+pub const FINGERPRINT_ERROR_NO_MATCH: u8 = 0x08;
+pub const FINGERPRINT_ERROR_NO_TEMPLATE_FOUND: u8 = 0x09;
+pub const FINGERPRINT_ADDR_CODE: u8 = 0x14;
+
+//AutoEnroll/AutoIdentify step codes (Payload[1] of each status packet)
+pub const AUTO_STEP_GET_IMAGE: u8 = 0x01;
+const AUTO_STEP_STORE_TEMPLATE: u8 = 0x06;
+const AUTO_STEP_SEARCH: u8 = 0x05;
+
+//AutoEnroll parameter: overwrite the slot like the manual flow does
+pub const AUTO_ENROLL_ALLOW_OVERWRITE: u16 = 0x0008;
+pub const AUTO_ENROLL_CAPTURES: u8 = 0x02;
+pub const AUTO_IDENTIFY_WHOLE_LIBRARY: u16 = 0xFFFF;
+//AutoIdentify capture count: one capture per identification
+pub const AUTO_IDENTIFY_CAPTURES: u8 = 0x01;
+
+// view system info
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemParameters {
+    pub status_register: u16,
+    pub system_id: u16,
+    pub storage_capacity: u16,
+    pub security_level: u16,
+    pub device_address: u32,
+    pub packet_length: u16,
+    pub baud_rate: u16,
+}
+
+// product info returned by ReadProdInfo on newer modules (R503 etc)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProductInfo {
+    pub module_type: String,
+    pub batch_number: String,
+    pub serial_number: String,
+    pub hardware_version: (u8, u8),
+    pub sensor_type: String,
+    pub sensor_width: u16,
+    pub sensor_height: u16,
+    pub template_size: u16,
+    pub database_size: u16,
+}
+
+//checksum over packet type, length and payload, as sent at the end of every packet
+pub fn checksum(packet_type: u8, payload: &[u8]) -> u16 {
+    let mut sum: u16 = packet_type as u16;
+    sum = sum.wrapping_add((payload.len() + 2) as u16);
+
+    for &byte in payload {
+        sum = sum.wrapping_add(byte as u16);
+    }
+    sum
+}
+
+//frames a payload into a full packet
+pub fn encode_packet(address: u32, packet_type: u8, payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::new();
+    packet.push(PACKET_START_CODE_1);
+    packet.push(PACKET_START_CODE_2);
+    packet.extend_from_slice(&address.to_be_bytes());
+    packet.push(packet_type);
+    let packet_length = (payload.len() + 2) as u16;
+    packet.extend_from_slice(&packet_length.to_be_bytes());
+    packet.extend_from_slice(payload);
+    packet.extend_from_slice(&checksum(packet_type, payload).to_be_bytes());
+
+    packet
+}
+
+//checks a received packet and splits out its type and payload
+pub fn decode_packet<'a, E>(
+    address: u32,
+    raw_bytes: &'a [u8],
+    command: &'static str,
+) -> Result<(u8, &'a [u8]), Error<E>> {
+    // line noise or a reply cut short, worth asking again
+    let malformed =
+        |reason: &str| Error::Malformed { command, reason: reason.to_string(), received: raw_bytes.to_vec() };
+
+    if raw_bytes.len() < 12 {
+        return Err(malformed("packet too short"));
+    }
+
+    if raw_bytes[0] != PACKET_START_CODE_1 || raw_bytes[1] != PACKET_START_CODE_2 {
+        return Err(malformed("invalid start code"));
+    }
+
+    let received_address = u32::from_be_bytes([raw_bytes[2], raw_bytes[3], raw_bytes[4], raw_bytes[5]]);
+    if received_address != address {
+        return Err(Error::AddressMismatch { command, expected: address, found: received_address });
+    }
+
+    let packet_type = raw_bytes[6];
+    let packet_length = u16::from_be_bytes([raw_bytes[7], raw_bytes[8]]);
+
+    let expected_payload_len = match packet_length.checked_sub(2) {
+        Some(len) => len as usize,
+        None => return Err(malformed("invalid packet length")),
+    };
+
+    if raw_bytes.len() < 9 + expected_payload_len + 2 {
+        return Err(malformed("packet shorter than its length"));
+    }
+
+    let payload_start_idx = 9;
+    let payload_end_idx = payload_start_idx + expected_payload_len;
+    let payload = &raw_bytes[payload_start_idx..payload_end_idx];
+
+    let received_checksum = u16::from_be_bytes([raw_bytes[payload_end_idx], raw_bytes[payload_end_idx + 1]]);
+    let calculated_checksum = checksum(packet_type, payload);
+
+    if received_checksum != calculated_checksum {
+        return Err(Error::ChecksumMismatch {
+            command,
+            expected: calculated_checksum,
+            found: received_checksum,
+            received: raw_bytes.to_vec(),
+        });
+    }
+
+    Ok((packet_type, payload))
+}
+
+// the two feature buffers on the module that Img2Tz, Store, LoadChar and Search work on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharBuffer {
+    One = 0x01,
+    Two = 0x02,
+}
+
+// every instruction the driver knows, with its parameters (from the R305/R503 manuals)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    VerifyPassword(u32),
+    GetSystemParameters,
+    ReadProductInfo,
+    ReadImage,
+    ConvertImage { buffer: CharBuffer },
+    CreateTemplate,
+    StoreTemplate { buffer: CharBuffer, id: u16 },
+    LoadTemplate { buffer: CharBuffer, id: u16 },
+    Match,
+    Search { buffer: CharBuffer, start: u16, count: u16 },
+    // same result as Search, much faster on large libraries
+    HighSpeedSearch { buffer: CharBuffer, start: u16, count: u16 },
+    // followed by data packets from the module
+    UploadTemplate { buffer: CharBuffer },
+    // followed by data packets from the host
+    DownloadTemplate { buffer: CharBuffer },
+    ClearDatabase,
+    TemplateCount,
+    ReadIndexTable { page: u8 },
+    AuraLedConfig(AuraLedConfig),
+    AutoEnroll { id: u16, captures: u8, parameter: u16 },
+    AutoIdentify { security_level: u8, id: u16, captures: u8, parameter: u16 },
+}
+
+impl Command {
+    pub fn code(&self) -> u8 {
+        match self {
+            Command::ReadImage => 0x01,
+            Command::ConvertImage { .. } => 0x02,
+            Command::Match => 0x03,
+            Command::Search { .. } => 0x04,
+            Command::CreateTemplate => 0x05,
+            Command::StoreTemplate { .. } => 0x06,
+            Command::LoadTemplate { .. } => 0x07,
+            Command::UploadTemplate { .. } => 0x08,
+            Command::DownloadTemplate { .. } => 0x09,
+            Command::ClearDatabase => 0x0D,
+            Command::GetSystemParameters => 0x0F,
+            Command::VerifyPassword(_) => 0x13,
+            Command::HighSpeedSearch { .. } => 0x1B,
+            Command::TemplateCount => 0x1D,
+            Command::ReadIndexTable { .. } => 0x1F,
+            Command::AutoEnroll { .. } => 0x31,
+            Command::AutoIdentify { .. } => 0x32,
+            Command::AuraLedConfig(_) => 0x35,
+            Command::ReadProductInfo => 0x3C,
+        }
+    }
+
+    // instruction name as the manuals spell it, used in errors and logs
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::VerifyPassword(_) => "VfyPwd",
+            Command::GetSystemParameters => "ReadSysPara",
+            Command::ReadProductInfo => "ReadProdInfo",
+            Command::ReadImage => "GenImg",
+            Command::ConvertImage { .. } => "Img2Tz",
+            Command::CreateTemplate => "RegModel",
+            Command::StoreTemplate { .. } => "Store",
+            Command::LoadTemplate { .. } => "LoadChar",
+            Command::Match => "Match",
+            Command::Search { .. } => "Search",
+            Command::HighSpeedSearch { .. } => "HighSpeedSearch",
+            Command::UploadTemplate { .. } => "UpChar",
+            Command::DownloadTemplate { .. } => "DownChar",
+            Command::ClearDatabase => "Empty",
+            Command::TemplateCount => "TempleteNum",
+            Command::ReadIndexTable { .. } => "ReadIndexTable",
+            Command::AuraLedConfig(_) => "AuraLedConfig",
+            Command::AutoEnroll { .. } => "AutoEnroll",
+            Command::AutoIdentify { .. } => "AutoIdentify",
+        }
+    }
+
+    // payload of the command packet: instruction code followed by its parameters
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = vec![self.code()];
+
+        match self {
+            Command::VerifyPassword(password) => payload.extend_from_slice(&password.to_be_bytes()),
+            Command::ConvertImage { buffer }
+            | Command::UploadTemplate { buffer }
+            | Command::DownloadTemplate { buffer } => payload.push(*buffer as u8),
+            Command::ReadIndexTable { page } => payload.push(*page),
+            Command::StoreTemplate { buffer, id } | Command::LoadTemplate { buffer, id } => {
+                payload.push(*buffer as u8);
+                payload.extend_from_slice(&id.to_be_bytes());
+            }
+            Command::Search { buffer, start, count }
+            | Command::HighSpeedSearch { buffer, start, count } => {
+                payload.push(*buffer as u8);
+                payload.extend_from_slice(&start.to_be_bytes());
+                payload.extend_from_slice(&count.to_be_bytes());
+            }
+            Command::AuraLedConfig(config) => payload.extend_from_slice(&config.to_payload()),
+            Command::AutoEnroll { id, captures, parameter } => {
+                payload.extend_from_slice(&id.to_be_bytes());
+                payload.push(*captures);
+                payload.extend_from_slice(&parameter.to_be_bytes());
+            }
+            Command::AutoIdentify { security_level, id, captures, parameter } => {
+                payload.push(*security_level);
+                payload.extend_from_slice(&id.to_be_bytes());
+                payload.push(*captures);
+                payload.extend_from_slice(&parameter.to_be_bytes());
+            }
+            Command::GetSystemParameters
+            | Command::ReadProductInfo
+            | Command::ReadImage
+            | Command::CreateTemplate
+            | Command::Match
+            | Command::ClearDatabase
+            | Command::TemplateCount => {}
+        }
+
+        payload
+    }
+}
+
+// what an acknowledgement decodes to for the command that was sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Ok,
+    SystemParameters(SystemParameters),
+    ProductInfo(ProductInfo),
+    Match { id: u16, score: u16 },
+    // score of comparing CharBuffer1 against CharBuffer2
+    Score(u16),
+    TemplateCount(u16),
+    // occupancy bitmap of one index page, bit 0 of byte 0 is the page's first slot
+    IndexTable(Vec<u8>),
+    NoMatch,
+    // intermediate status of AutoEnroll/AutoIdentify, more packets follow
+    AutoStep { step: u8, detail: u8 },
+}
+
+impl Response {
+    // decodes an ack payload, any confirmation code other than OK becomes SensorError
+    pub fn decode<E>(command: &Command, payload: &[u8]) -> Result<Response, Error<E>> {
+        let code = expect_len(command, payload, 1, "confirmation code")?[0];
+
+        let searching = matches!(
+            command,
+            Command::Search { .. } | Command::HighSpeedSearch { .. } | Command::AutoIdentify { .. }
+        );
+        if searching && code == FINGERPRINT_ERROR_NO_TEMPLATE_FOUND {
+            return Ok(Response::NoMatch);
+        }
+        if *command == Command::Match && code == FINGERPRINT_ERROR_NO_MATCH {
+            return Ok(Response::NoMatch);
+        }
+        if code != FINGERPRINT_OK {
+            return Err(Error::SensorError(code));
+        }
+
+        match command {
+            Command::GetSystemParameters => decode_system_parameters(command, payload),
+            Command::ReadProductInfo => decode_product_info(command, payload),
+            Command::Match => {
+                let data = expect_len(command, payload, 3, "Match result")?;
+                Ok(Response::Score(u16::from_be_bytes([data[1], data[2]])))
+            }
+            Command::TemplateCount => {
+                let data = expect_len(command, payload, 3, "template count")?;
+                Ok(Response::TemplateCount(u16::from_be_bytes([data[1], data[2]])))
+            }
+            Command::ReadIndexTable { .. } => {
+                let data = expect_len(command, payload, 33, "index table")?;
+                Ok(Response::IndexTable(data[1..33].to_vec()))
+            }
+            Command::Search { .. } | Command::HighSpeedSearch { .. } => {
+                let data = expect_len(command, payload, 5, "Search result")?;
+                Ok(Response::Match {
+                    id: u16::from_be_bytes([data[1], data[2]]),
+                    score: u16::from_be_bytes([data[3], data[4]]),
+                })
+            }
+            Command::AutoEnroll { .. } => {
+                let data = expect_len(command, payload, 3, "AutoEnroll status")?;
+                match data[1] {
+                    AUTO_STEP_STORE_TEMPLATE => Ok(Response::Ok),
+                    step => Ok(Response::AutoStep { step, detail: data[2] }),
+                }
+            }
+            Command::AutoIdentify { .. } => {
+                let data = expect_len(command, payload, 2, "AutoIdentify status")?;
+                match data[1] {
+                    AUTO_STEP_SEARCH => {
+                        let data = expect_len(command, payload, 6, "AutoIdentify result")?;
+                        Ok(Response::Match {
+                            id: u16::from_be_bytes([data[2], data[3]]),
+                            score: u16::from_be_bytes([data[4], data[5]]),
+                        })
+                    }
+                    step => Ok(Response::AutoStep { step, detail: 0 }),
+                }
+            }
+            _ => Ok(Response::Ok),
+        }
+    }
+}
+
+// a reply cut short is malformed rather than a protocol error, so it is retried
+fn expect_len<'a, E>(command: &Command, payload: &'a [u8], len: usize, what: &str) -> Result<&'a [u8], Error<E>> {
+    if payload.len() < len {
+        return Err(Error::Malformed {
+            command: command.name(),
+            reason: format!("{} too short", what),
+            received: payload.to_vec(),
+        });
+    }
+    Ok(payload)
+}
+
+fn decode_system_parameters<E>(command: &Command, payload: &[u8]) -> Result<Response, Error<E>> {
+    let data = expect_len(command, payload, 17, "payload")?;
+
+    Ok(Response::SystemParameters(SystemParameters {
+        status_register: u16::from_be_bytes([data[1], data[2]]),
+        system_id: u16::from_be_bytes([data[3], data[4]]),
+        storage_capacity: u16::from_be_bytes([data[5], data[6]]),
+        security_level: u16::from_be_bytes([data[7], data[8]]),
+        device_address: u32::from_be_bytes([data[9], data[10], data[11], data[12]]),
+        packet_length: u16::from_be_bytes([data[13], data[14]]),
+        baud_rate: u16::from_be_bytes([data[15], data[16]]),
+    }))
+}
+
+fn decode_product_info<E>(command: &Command, payload: &[u8]) -> Result<Response, Error<E>> {
+    let data = expect_len(command, payload, 47, "product info")?;
+    let text = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes).trim_end_matches(['\0', ' ']).to_string()
+    };
+
+    Ok(Response::ProductInfo(ProductInfo {
+        module_type: text(&data[1..17]),
+        batch_number: text(&data[17..21]),
+        serial_number: text(&data[21..29]),
+        hardware_version: (data[29], data[30]),
+        sensor_type: text(&data[31..39]),
+        sensor_width: u16::from_be_bytes([data[39], data[40]]),
+        sensor_height: u16::from_be_bytes([data[41], data[42]]),
+        template_size: u16::from_be_bytes([data[43], data[44]]),
+        database_size: u16::from_be_bytes([data[45], data[46]]),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: u32 = 0xFFFFFFFF;
+
+    fn decode(command: &Command, payload: &[u8]) -> Result<Response, Error<()>> {
+        Response::decode(command, payload)
+    }
+
+    fn ack(payload: &[u8]) -> Vec<u8> {
+        encode_packet(ADDRESS, ACK_PACKET, payload)
+    }
+
+    #[test]
+    fn encodes_commands_as_the_manual_lays_them_out() {
+        assert_eq!(Command::ReadImage.encode(), [0x01]);
+        assert_eq!(Command::VerifyPassword(0x01020304).encode(), [0x13, 0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(Command::ConvertImage { buffer: CharBuffer::Two }.encode(), [0x02, 0x02]);
+        assert_eq!(Command::StoreTemplate { buffer: CharBuffer::One, id: 0x0102 }.encode(), [0x06, 0x01, 0x01, 0x02]);
+        assert_eq!(
+            Command::Search { buffer: CharBuffer::One, start: 0, count: 0x00A3 }.encode(),
+            [0x04, 0x01, 0x00, 0x00, 0x00, 0xA3]
+        );
+        assert_eq!(
+            Command::HighSpeedSearch { buffer: CharBuffer::Two, start: 0x0010, count: 0x0020 }.encode(),
+            [0x1B, 0x02, 0x00, 0x10, 0x00, 0x20]
+        );
+        assert_eq!(Command::ReadIndexTable { page: 3 }.encode(), [0x1F, 0x03]);
+        assert_eq!(
+            Command::AutoEnroll { id: 0x0007, captures: AUTO_ENROLL_CAPTURES, parameter: AUTO_ENROLL_ALLOW_OVERWRITE }
+                .encode(),
+            [0x31, 0x00, 0x07, 0x02, 0x00, 0x08]
+        );
+        assert_eq!(
+            Command::AutoIdentify {
+                security_level: 3,
+                id: AUTO_IDENTIFY_WHOLE_LIBRARY,
+                captures: AUTO_IDENTIFY_CAPTURES,
+                parameter: 0,
+            }
+            .encode(),
+            [0x32, 0x03, 0xFF, 0xFF, 0x01, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn frames_packets_with_length_and_checksum() {
+        // GenImg exactly as the R305 manual prints it
+        assert_eq!(
+            encode_packet(ADDRESS, COMMAND_PACKET, &Command::ReadImage.encode()),
+            [0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x03, 0x01, 0x00, 0x05]
+        );
+    }
+
+    #[test]
+    fn decodes_a_packet_it_framed() {
+        let packet = ack(&[FINGERPRINT_OK, 0x00, 0x05]);
+        let (packet_type, payload) = decode_packet::<()>(ADDRESS, &packet, "Test").unwrap();
+        assert_eq!(packet_type, ACK_PACKET);
+        assert_eq!(payload, [FINGERPRINT_OK, 0x00, 0x05]);
+    }
+
+    #[test]
+    fn short_or_garbled_packets_are_malformed() {
+        let packet = ack(&[FINGERPRINT_OK, 0x00, 0x05]);
+
+        let truncated = decode_packet::<()>(ADDRESS, &packet[..packet.len() - 1], "Test");
+        assert!(matches!(truncated, Err(Error::Malformed { command: "Test", .. })));
+
+        let too_short = decode_packet::<()>(ADDRESS, &packet[..6], "Test");
+        assert!(matches!(too_short, Err(Error::Malformed { .. })));
+
+        let mut bad_start = packet.clone();
+        bad_start[0] = 0x00;
+        assert!(matches!(decode_packet::<()>(ADDRESS, &bad_start, "Test"), Err(Error::Malformed { .. })));
+
+        // a length below the checksum's two bytes
+        let mut bad_length = packet.clone();
+        bad_length[7..9].copy_from_slice(&[0x00, 0x01]);
+        assert!(matches!(decode_packet::<()>(ADDRESS, &bad_length, "Test"), Err(Error::Malformed { .. })));
+    }
+
+    #[test]
+    fn corrupted_packets_fail_their_checksum() {
+        let mut packet = ack(&[FINGERPRINT_OK, 0x00, 0x05]);
+        packet[10] ^= 0xFF;
+        assert!(matches!(
+            decode_packet::<()>(ADDRESS, &packet, "Test"),
+            Err(Error::ChecksumMismatch { command: "Test", .. })
+        ));
+    }
+
+    #[test]
+    fn replies_from_another_address_are_rejected() {
+        let packet = encode_packet(0x12345678, ACK_PACKET, &[FINGERPRINT_OK]);
+        assert!(matches!(
+            decode_packet::<()>(ADDRESS, &packet, "Test"),
+            Err(Error::AddressMismatch { expected: ADDRESS, found: 0x12345678, .. })
+        ));
+    }
+
+    #[test]
+    fn confirmation_codes_other_than_ok_are_sensor_errors() {
+        assert_eq!(decode(&Command::ReadImage, &[FINGERPRINT_OK]).unwrap(), Response::Ok);
+        assert!(matches!(
+            decode(&Command::ReadImage, &[FINGERPRINT_ERROR_NO_FINGER]),
+            Err(Error::SensorError(FINGERPRINT_ERROR_NO_FINGER))
+        ));
+        // no match is an answer, not an error, for the commands that search or compare
+        let search = Command::Search { buffer: CharBuffer::One, start: 0, count: 10 };
+        assert_eq!(decode(&search, &[FINGERPRINT_ERROR_NO_TEMPLATE_FOUND]).unwrap(), Response::NoMatch);
+        assert_eq!(decode(&Command::Match, &[FINGERPRINT_ERROR_NO_MATCH]).unwrap(), Response::NoMatch);
+        assert!(matches!(
+            decode(&Command::ReadImage, &[FINGERPRINT_ERROR_NO_TEMPLATE_FOUND]),
+            Err(Error::SensorError(FINGERPRINT_ERROR_NO_TEMPLATE_FOUND))
+        ));
+    }
+
+    #[test]
+    fn decodes_result_payloads() {
+        let search = Command::Search { buffer: CharBuffer::One, start: 0, count: 10 };
+        assert_eq!(
+            decode(&search, &[FINGERPRINT_OK, 0x00, 0x05, 0x00, 0x64]).unwrap(),
+            Response::Match { id: 5, score: 100 }
+        );
+        assert_eq!(decode(&Command::Match, &[FINGERPRINT_OK, 0x00, 0x32]).unwrap(), Response::Score(50));
+        assert_eq!(
+            decode(&Command::TemplateCount, &[FINGERPRINT_OK, 0x01, 0x00]).unwrap(),
+            Response::TemplateCount(256)
+        );
+
+        let identify = Command::AutoIdentify { security_level: 3, id: 0xFFFF, captures: 1, parameter: 0 };
+        assert_eq!(
+            decode(&identify, &[FINGERPRINT_OK, AUTO_STEP_GET_IMAGE]).unwrap(),
+            Response::AutoStep { step: AUTO_STEP_GET_IMAGE, detail: 0 }
+        );
+        assert_eq!(
+            decode(&identify, &[FINGERPRINT_OK, AUTO_STEP_SEARCH, 0x00, 0x03, 0x00, 0x32]).unwrap(),
+            Response::Match { id: 3, score: 50 }
+        );
+    }
+
+    #[test]
+    fn truncated_payloads_are_malformed() {
+        let search = Command::Search { buffer: CharBuffer::One, start: 0, count: 10 };
+        assert!(matches!(decode(&Command::ReadImage, &[]), Err(Error::Malformed { command: "GenImg", .. })));
+        assert!(matches!(
+            decode(&search, &[FINGERPRINT_OK, 0x00, 0x05]),
+            Err(Error::Malformed { command: "Search", .. })
+        ));
+        assert!(matches!(decode(&Command::GetSystemParameters, &[FINGERPRINT_OK; 10]), Err(Error::Malformed { .. })));
+        assert!(matches!(
+            decode(&Command::ReadIndexTable { page: 0 }, &[FINGERPRINT_OK; 32]),
+            Err(Error::Malformed { .. })
+        ));
+
+        let identify = Command::AutoIdentify { security_level: 3, id: 0xFFFF, captures: 1, parameter: 0 };
+        assert!(matches!(decode(&identify, &[FINGERPRINT_OK, AUTO_STEP_SEARCH, 0x00]), Err(Error::Malformed { .. })));
+    }
+}
@@ -0,0 +1,62 @@
+use attendpi::sensors::r305_fingerprint_sensor::error::FingerprintError;
+use attendpi::sensors::r305_fingerprint_sensor::lib::{FingerprintSensor, SearchMode};
+use attendpi::sensors::r305_fingerprint_sensor::protocol::CharBuffer;
+use attendpi::sensors::r305_fingerprint_sensor::trace::{command_latencies, read_trace};
+use std::io;
+use std::time::{Duration, Instant};
+
+// captures one finger and times a whole library search with each search mode the sensor has
+pub fn bench_search(fingerprint_sensor: &mut FingerprintSensor, rounds: u32) -> Result<(), FingerprintError> {
+    let capacity = fingerprint_sensor.parameters().storage_capacity;
+    let enrolled = fingerprint_sensor.template_count()?;
+    let original_mode = fingerprint_sensor.search_mode();
+
+    println!("[INFO] Place a finger on the sensor");
+    fingerprint_sensor.capture_image()?;
+    fingerprint_sensor.generate_char(CharBuffer::One)?;
+    println!("[INFO] Searching {} enrolled of {} slots, {} rounds", enrolled, capacity, rounds);
+
+    for mode in [SearchMode::Normal, SearchMode::HighSpeed] {
+        if let Err(e) = fingerprint_sensor.set_search_mode(mode) {
+            println!("[INFO] {:?}: {}", mode, e);
+            continue;
+        }
+
+        let mut total = Duration::ZERO;
+        let mut slowest = Duration::ZERO;
+        let mut result = None;
+        for _ in 0..rounds {
+            let started = Instant::now();
+            result = fingerprint_sensor.search_char(CharBuffer::One, 0, capacity)?;
+            let elapsed = started.elapsed();
+            total += elapsed;
+            slowest = slowest.max(elapsed);
+        }
+
+        println!(
+            "[INFO] {:?}: average {:?}, slowest {:?}, result {:?}",
+            mode,
+            total / rounds.max(1),
+            slowest,
+            result
+        );
+    }
+
+    fingerprint_sensor.set_search_mode(original_mode)
+}
+
+// prints per instruction ack latency of a recorded trace
+pub fn trace_stats(path: &str) -> io::Result<()> {
+    let records = read_trace(path)?;
+
+    for latency in command_latencies(&records) {
+        println!(
+            "[INFO] 0x{:02X}: {} sent, average {}us, slowest {}us",
+            latency.instruction,
+            latency.count,
+            latency.average_micros(),
+            latency.max_micros
+        );
+    }
+    Ok(())
+}
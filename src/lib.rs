@@ -0,0 +1,6 @@
+// hardware drivers and attendance building blocks, usable without the kiosk binary
+pub mod network;
+pub mod registry;
+#[cfg(feature = "serial-sensor")]
+pub mod sensor_manager;
+pub mod sensors;
@@ -1,17 +1,93 @@
-mod sensors;
+mod bench;
 mod test;
 
-use crate::sensors::keypad::Keypad;
 use crate::test::test;
-use sensors::r305_fingerprint_sensor::lib::FingerprintSensor;
-use sensors::ssd1305_display::SSD1305Display;
+use attendpi::registry::UserRegistry;
+use attendpi::sensors::display::error::DisplayError;
+use attendpi::sensors::display::framebuffer::FrameBuffer;
+#[cfg(feature = "serial-sensor")]
+use attendpi::sensors::display::indicator::FINGER;
+#[cfg(feature = "serial-sensor")]
+use attendpi::sensors::display::layout::Layout;
+use attendpi::sensors::display::renderer::Renderer;
+use attendpi::sensors::display::screensaver::{SaverMode, Screensaver};
+#[cfg(feature = "serial-sensor")]
+use attendpi::sensors::display::status::SensorState;
+use attendpi::sensors::display::status::Status;
+use attendpi::sensors::display::terminal::{TerminalDisplay, TerminalStyle};
+use attendpi::sensors::display::Display;
+#[cfg(feature = "serial-sensor")]
+use attendpi::sensor_manager::{SensorConfig, SensorEvent, SensorManager};
+#[cfg(feature = "simulator")]
+use attendpi::sensors::r305_fingerprint_sensor::emulator::{EmulatedModel, EmulatedSensor};
+use attendpi::sensors::r305_fingerprint_sensor::error::FingerprintError;
+use attendpi::sensors::r305_fingerprint_sensor::backup::TemplateArchive;
+use attendpi::sensors::r305_fingerprint_sensor::lib::FingerprintSensor;
+use attendpi::sensors::r305_fingerprint_sensor::sharding::TemplatePage;
+#[cfg(feature = "rpi-gpio")]
+use attendpi::sensors::r305_fingerprint_sensor::touch::TouchPin;
+use attendpi::sensors::r305_fingerprint_sensor::trace::{ReplayTransport, TracingTransport};
+#[cfg(feature = "serial-sensor")]
+use attendpi::sensors::r305_fingerprint_sensor::transport::open_first_port;
+use attendpi::sensors::r305_fingerprint_sensor::transport::Transport;
+#[cfg(feature = "rpi-gpio")]
+use rppal::gpio::Level;
+#[cfg(feature = "serial-sensor")]
+use attendpi::network::network_state;
+#[cfg(feature = "serial-sensor")]
+use std::collections::HashSet;
+use std::env;
+use std::io;
+#[cfg(feature = "serial-sensor")]
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+// real peripherals when built for the Pi, console stand-ins otherwise
+#[cfg(feature = "rpi-gpio")]
+use attendpi::sensors::keypad::Keypad;
+#[cfg(all(feature = "simulator", not(feature = "rpi-gpio")))]
+use attendpi::sensors::simulator::ConsoleKeypad as Keypad;
+#[cfg(feature = "i2c-display")]
+use attendpi::sensors::ssd1305_display::{Ssd1305Config, SSD1305Display};
+#[cfg(all(feature = "simulator", not(feature = "i2c-display")))]
+use attendpi::sensors::simulator::ConsoleDisplay;
+
+#[cfg(not(any(feature = "rpi-gpio", feature = "simulator")))]
+compile_error!("attendpi needs a keypad: enable the rpi-gpio or simulator feature");
+#[cfg(not(any(feature = "i2c-display", feature = "simulator")))]
+compile_error!("attendpi needs a display: enable the i2c-display or simulator feature");
+
+#[cfg(feature = "simulator")]
+const EMULATOR_CAPACITY: u16 = 1000;
+
+// how often the entrance screen checks the network while no sensor reports anything
+#[cfg(feature = "serial-sensor")]
+const NETWORK_CHECK: Duration = Duration::from_secs(5);
 
 fn main() {
     let baud_rate: u32 = 57600;
     let address: u32 = 0xFFFFFFFF;
     let password: u32 = 0x00000000;
 
-    let mut fingerprint_sensor = match FingerprintSensor::new(baud_rate, address, password) {
+    // ATTENDPI_SENSORS=<id=port[,address[,password]];...> polls several sensors at once
+    #[cfg(feature = "serial-sensor")]
+    if let Ok(spec) = env::var("ATTENDPI_SENSORS") {
+        run_entrances(&spec);
+        return;
+    }
+
+    // `attendpi trace-stats <file>` only reads a trace, no sensor needed
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let (Some("trace-stats"), Some(path)) = (args.first().map(String::as_str), args.get(1)) {
+        if let Err(e) = bench::trace_stats(path) {
+            println!("Error reading packet trace {}", e);
+        }
+        return;
+    }
+
+    let mut fingerprint_sensor = match open_transport(baud_rate)
+        .and_then(|port| FingerprintSensor::with_transport(port, address, password))
+    {
         Ok(sensor) => sensor,
         Err(e) => {
             println!("Error initialising {}", e);
@@ -19,11 +95,129 @@ fn main() {
         }
     };
 
-    fingerprint_sensor.delete_all().unwrap();
+    // ATTENDPI_TOUCH_PIN=<bcm pin> waits on the sensor's touch output instead of polling
+    #[cfg(feature = "rpi-gpio")]
+    if let Ok(pin) = env::var("ATTENDPI_TOUCH_PIN") {
+        match pin.parse::<u8>() {
+            Ok(pin) => match TouchPin::new(pin, Level::High) {
+                Ok(touch_pin) => fingerprint_sensor.set_touch_pin(touch_pin),
+                Err(e) => println!("[WARNING] Touch pin {} unavailable: {}", pin, e),
+            },
+            Err(_) => println!("[WARNING] Invalid touch pin: {}", pin),
+        }
+    }
+
+    match load_pages() {
+        Ok(pages) if pages.is_empty() => {}
+        Ok(pages) => {
+            if let Err(e) = fingerprint_sensor.set_pages(pages) {
+                println!("Error paging templates {}", e);
+                return;
+            }
+        }
+        Err(e) => {
+            println!("Error reading template pages {}", e);
+            return;
+        }
+    }
+
+    // ATTENDPI_REGISTRY=<file> overrides where enrolled users are kept
+    let registry_path = env::var("ATTENDPI_REGISTRY").unwrap_or_else(|_| "users.tsv".to_string());
+    let mut registry = match UserRegistry::load(&registry_path) {
+        Ok(registry) => registry,
+        Err(e) => {
+            println!("Error loading user registry {}", e);
+            return;
+        }
+    };
+
+    // `attendpi backup <file>` / `attendpi restore <file>` / `attendpi wipe --confirm` /
+    // `attendpi prune --confirm` / `attendpi bench-search [rounds]` run once and exit
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("backup"), Some(path)) => {
+            match fingerprint_sensor.backup(path) {
+                Ok(count) => println!("[INFO] Saved {} templates to {}", count, path),
+                Err(e) => println!("Error backing up templates {}", e),
+            }
+            return;
+        }
+        (Some("restore"), Some(path)) => {
+            match fingerprint_sensor.restore(path) {
+                Ok(count) => println!("[INFO] Restored {} templates from {}", count, path),
+                Err(e) => println!("Error restoring templates {}", e),
+            }
+            return;
+        }
+        (Some("wipe"), Some(flag)) if flag == "--confirm" => {
+            match wipe(&mut fingerprint_sensor, &mut registry) {
+                Ok(_) => println!("[INFO] Deleted every enrolled fingerprint"),
+                Err(e) => println!("Error wiping fingerprints {}", e),
+            }
+            return;
+        }
+        (Some("prune"), Some(flag)) if flag == "--confirm" => {
+            match prune(&mut fingerprint_sensor, &mut registry) {
+                Ok(pruned) => println!("[INFO] Removed {} users whose template is gone: {:?}", pruned.len(), pruned),
+                Err(e) => println!("Error pruning user registry {}", e),
+            }
+            return;
+        }
+        (Some("bench-search"), _) if !real_sensor() => {
+            println!("Refusing to benchmark without a real sensor, emulated and replayed search times are made up");
+            return;
+        }
+        (Some("bench-search"), rounds) => {
+            let rounds = rounds.and_then(|r| r.parse().ok()).unwrap_or(10);
+            if let Err(e) = bench::bench_search(&mut fingerprint_sensor, rounds) {
+                println!("Error benchmarking search {}", e);
+            }
+            return;
+        }
+        (Some("wipe"), _) => {
+            println!("Refusing to wipe without --confirm, this deletes every enrolled fingerprint");
+            return;
+        }
+        (Some("prune"), _) => {
+            println!("Refusing to prune without --confirm, this forgets every user missing from the sensor");
+            return;
+        }
+        (Some(command), _) => {
+            println!(
+                "Usage: attendpi [backup <file> | restore <file> | wipe --confirm | prune --confirm | bench-search [rounds] | trace-stats <file>], got {}",
+                command
+            );
+            return;
+        }
+        (None, _) => {}
+    }
+
+    // unknown templates on the sensor are registered, users missing from it are only reported
+    // since the registry is what a restore needs, `prune --confirm` drops them. A paged
+    // sensor's slots are a cache of the pages, the registry is keyed by template id instead
+    match fingerprint_sensor.occupied_slots() {
+        Ok(_) if fingerprint_sensor.is_paged() => println!("[INFO] {} registered users", registry.len()),
+        Ok(occupied) => {
+            let result = registry.reconcile(&occupied);
+            for slot in &result.adopted {
+                println!("[WARNING] Slot {} is enrolled but not registered, keeping it", slot);
+            }
+            for slot in &result.missing {
+                println!("[WARNING] Slot {} is registered but missing from the sensor, keeping it", slot);
+            }
+            if let Err(e) = registry.save() {
+                println!("[WARNING] Could not save user registry: {}", e);
+            }
+            println!("[INFO] {} enrolled users", registry.len());
+        }
+        Err(e) => {
+            println!("Error reading enrolled fingerprints {}", e);
+            return;
+        }
+    }
 
     let mut keypad = Keypad::new();
 
-    let mut display = match SSD1305Display::new() {
+    let mut display = match open_display() {
         Ok(display) => display,
         Err(e) => {
             println!("Error Initialising display {}", e);
@@ -31,9 +225,323 @@ fn main() {
         }
     };
 
-    test(
+    if let Err(e) = test(
         &mut fingerprint_sensor,
         &mut display,
         &mut keypad,
-    )
+        &mut registry,
+    ) {
+        println!("Error drawing on the display {}", e);
+    }
+    display.stop();
+}
+
+// ATTENDPI_PAGES=<name=archive;...> searches template archives written by `backup` as pages
+// swapped into the top slots of the sensor, for libraries bigger than it holds. Templates enrolled
+// below them are kept, a template in the way that is in no page stops startup
+fn load_pages() -> Result<Vec<TemplatePage>, FingerprintError> {
+    let Ok(spec) = env::var("ATTENDPI_PAGES") else {
+        return Ok(Vec::new());
+    };
+
+    spec.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, path) = entry
+                .split_once('=')
+                .ok_or_else(|| FingerprintError::Archive(format!("Expected name=file in \"{}\"", entry)))?;
+            Ok(TemplatePage::from_archive(name.trim(), TemplateArchive::read(path.trim())?))
+        })
+        .collect()
+}
+
+// forgets users whose template is gone from the sensor, never done implicitly
+fn prune(fingerprint_sensor: &mut FingerprintSensor, registry: &mut UserRegistry) -> Result<Vec<u16>, String> {
+    let occupied = fingerprint_sensor.occupied_slots().map_err(|e| e.to_string())?;
+    let pruned = registry.prune(&occupied);
+    registry.save().map_err(|e| e.to_string())?;
+    Ok(pruned)
+}
+
+// the only way the library gets emptied, never done implicitly
+fn wipe(fingerprint_sensor: &mut FingerprintSensor, registry: &mut UserRegistry) -> Result<(), String> {
+    fingerprint_sensor.delete_all().map_err(|e| e.to_string())?;
+    registry.clear();
+    registry.save().map_err(|e| e.to_string())
+}
+
+// ATTENDPI_DISPLAY_SIZE=<width>x<height> sets the panel size (128x32 by default),
+// ATTENDPI_SNAPSHOTS=<dir> draws into an in-memory framebuffer of that size and saves every
+// screen there as a PNG, ATTENDPI_TERMINAL=blocks|braille prints every screen to the terminal.
+// whichever it is gets drawn from a render thread that keeps long text scrolling, under a
+// status bar, with a screensaver
+fn open_display() -> Result<Renderer, DisplayError> {
+    let (width, height) = match env::var("ATTENDPI_DISPLAY_SIZE") {
+        Ok(size) => parse_pair(&size, 'x')?,
+        Err(_) => (128, 32),
+    };
+
+    let display: Box<dyn Display + Send> = if let Ok(dir) = env::var("ATTENDPI_SNAPSHOTS") {
+        Box::new(FrameBuffer::with_size(width, height).with_snapshots(dir)?)
+    } else if let Ok(style) = env::var("ATTENDPI_TERMINAL") {
+        let style = TerminalStyle::parse(&style).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown terminal style \"{}\"", style))
+        })?;
+        Box::new(TerminalDisplay::new(width, height, style))
+    } else {
+        open_panel(width, height)?
+    };
+
+    // sensors are opened before the display, so the bar starts out connected
+    let mut renderer = Renderer::start(display);
+    renderer.set_status(&Status::default())?;
+    renderer.set_screensaver(screensaver()?)?;
+    Ok(renderer)
+}
+
+// ATTENDPI_SCREENSAVER=<dim seconds>,<saver seconds>[,blank|clock] sets how long the screen
+// may sit idle before it dims and then before the saver replaces it (30,120,clock by default),
+// ATTENDPI_SCREENSAVER=off keeps it on
+fn screensaver() -> io::Result<Option<Screensaver>> {
+    let Ok(spec) = env::var("ATTENDPI_SCREENSAVER") else {
+        return Ok(Some(Screensaver::default()));
+    };
+    if spec.trim().eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+
+    let parts: Vec<&str> = spec.split(',').collect();
+    let (dim, saver) = match parts.as_slice() {
+        [dim, saver] | [dim, saver, _] => (parse_number(dim)?, parse_number(saver)?),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Expected <dim>,<saver>[,blank|clock], got \"{}\"", spec),
+            ));
+        }
+    };
+    let mode = match parts.get(2) {
+        Some(mode) => SaverMode::parse(mode).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown screensaver \"{}\"", mode))
+        })?,
+        None => Screensaver::default().mode,
+    };
+
+    Ok(Some(Screensaver {
+        dim_after: Duration::from_secs(dim as u64),
+        saver_after: Duration::from_secs(saver as u64),
+        mode,
+    }))
+}
+
+// ATTENDPI_DISPLAY_OFFSET=<column>[,<row>] sets where the panel starts in the controller's RAM
+#[cfg(feature = "i2c-display")]
+fn open_panel(width: u32, height: u32) -> Result<Box<dyn Display + Send>, DisplayError> {
+    let mut config = Ssd1305Config { width, height, ..Default::default() };
+    if let Ok(offset) = env::var("ATTENDPI_DISPLAY_OFFSET") {
+        let (column, row) = match offset.split_once(',') {
+            Some(_) => parse_pair(&offset, ',')?,
+            None => (parse_number(&offset)?, config.row_offset as u32),
+        };
+        let out_of_range = |_| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Display offset \"{}\" is out of range", offset))
+        };
+        config.column_offset = u8::try_from(column).map_err(out_of_range)?;
+        config.row_offset = u8::try_from(row).map_err(out_of_range)?;
+    }
+
+    Ok(Box::new(SSD1305Display::with_config(config)?))
+}
+
+#[cfg(all(feature = "simulator", not(feature = "i2c-display")))]
+fn open_panel(_width: u32, _height: u32) -> Result<Box<dyn Display + Send>, DisplayError> {
+    Ok(Box::new(ConsoleDisplay::new()?))
+}
+
+fn parse_pair(text: &str, separator: char) -> io::Result<(u32, u32)> {
+    let (first, second) = text.split_once(separator).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("Expected <a>{}<b>, got \"{}\"", separator, text))
+    })?;
+    Ok((parse_number(first)?, parse_number(second)?))
+}
+
+fn parse_number(text: &str) -> io::Result<u32> {
+    text.trim()
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid number \"{}\"", text)))
+}
+
+// whether the sensor is a module on a serial port, not the emulator or a replayed trace
+fn real_sensor() -> bool {
+    let emulated = cfg!(feature = "simulator") && env::var("ATTENDPI_EMULATOR").is_ok();
+    cfg!(feature = "serial-sensor") && !emulated && env::var("ATTENDPI_REPLAY").is_err()
+}
+
+// ATTENDPI_REPLAY=<file> replays a recorded trace instead of using the sensor,
+// ATTENDPI_EMULATOR=<enrolled> talks to an emulated sensor with a finger on its last template,
+// the default without serial-sensor. ATTENDPI_TRACE=<file> records every packet exchanged
+fn open_transport(baud_rate: u32) -> Result<Box<dyn Transport>, FingerprintError> {
+    if let Ok(path) = env::var("ATTENDPI_REPLAY") {
+        println!("[INFO] Replaying packet trace from {}", path);
+        return Ok(Box::new(ReplayTransport::open(path)?));
+    }
+
+    #[cfg(feature = "simulator")]
+    let emulated = env::var("ATTENDPI_EMULATOR").ok().map(|enrolled| open_emulator(enrolled.parse().unwrap_or(0)));
+    #[cfg(not(feature = "simulator"))]
+    let emulated = None;
+
+    let port = match emulated {
+        Some(port) => port,
+        None => default_port(baud_rate)?,
+    };
+
+    match env::var("ATTENDPI_TRACE") {
+        Ok(path) => {
+            println!("[INFO] Recording packet trace to {}", path);
+            Ok(Box::new(TracingTransport::new(port, path)?))
+        }
+        Err(_) => Ok(port),
+    }
+}
+
+#[cfg(feature = "serial-sensor")]
+fn default_port(baud_rate: u32) -> Result<Box<dyn Transport>, FingerprintError> {
+    open_first_port(baud_rate)
+}
+
+#[cfg(all(feature = "simulator", not(feature = "serial-sensor")))]
+fn default_port(_baud_rate: u32) -> Result<Box<dyn Transport>, FingerprintError> {
+    Ok(open_emulator(0))
+}
+
+// only a replayed trace can stand in for the sensor
+#[cfg(not(any(feature = "simulator", feature = "serial-sensor")))]
+fn default_port(_baud_rate: u32) -> Result<Box<dyn Transport>, FingerprintError> {
+    Err(FingerprintError::NoFingerprintSensors)
+}
+
+#[cfg(feature = "simulator")]
+fn open_emulator(enrolled: u16) -> Box<dyn Transport> {
+    let enrolled = enrolled.min(EMULATOR_CAPACITY);
+    println!("[INFO] Using an emulated sensor with {} templates", enrolled);
+    let mut emulator = EmulatedSensor::new(EmulatedModel::R305, 0xFFFFFFFF, 0, EMULATOR_CAPACITY);
+    emulator.fill_library(enrolled);
+    emulator.handle().place_finger(enrolled.saturating_sub(1) as u32);
+    Box::new(emulator)
+}
+
+// reports matches from every configured sensor on the display
+#[cfg(feature = "serial-sensor")]
+fn run_entrances(spec: &str) {
+    let configs = match SensorConfig::parse_list(spec) {
+        Ok(configs) if !configs.is_empty() => configs,
+        Ok(_) => {
+            println!("Error ATTENDPI_SENSORS has no sensors");
+            return;
+        }
+        Err(e) => {
+            println!("Error parsing ATTENDPI_SENSORS {}", e);
+            return;
+        }
+    };
+
+    let pages = match load_pages() {
+        Ok(pages) => pages,
+        Err(e) => {
+            println!("Error reading template pages {}", e);
+            return;
+        }
+    };
+
+    let manager = match SensorManager::start(configs, pages) {
+        Ok(manager) => manager,
+        Err(e) => {
+            println!("Error initialising {}", e);
+            return;
+        }
+    };
+
+    let mut display = match open_display() {
+        Ok(display) => display,
+        Err(e) => {
+            println!("Error Initialising display {}", e);
+            manager.stop();
+            return;
+        }
+    };
+
+    if let Err(e) = show_events(&mut display, &manager).and_then(|()| display.cleanup()) {
+        println!("Error drawing on the display {}", e);
+    }
+    manager.stop();
+    display.stop();
+}
+
+// shows what every sensor reports until they have all stopped
+#[cfg(feature = "serial-sensor")]
+fn show_events(display: &mut dyn Display, manager: &SensorManager) -> Result<(), DisplayError> {
+    display.show(&Layout::new().animation(&FINGER).centred("Detecting Fingerprints..."))?;
+
+    // the bar shows the sensor lost while any of them is, and the network as last checked
+    let mut status = Status::default();
+    let mut lost = HashSet::new();
+    loop {
+        let event = match manager.events().recv_timeout(NETWORK_CHECK) {
+            Ok(event) => Some(event),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        match &event {
+            Some(SensorEvent::Error { sensor_id, lost: true, .. }) => lost.insert(sensor_id.clone()),
+            Some(
+                SensorEvent::Touched { sensor_id }
+                | SensorEvent::Matched { sensor_id, .. }
+                | SensorEvent::Rejected { sensor_id }
+                | SensorEvent::Error { sensor_id, .. },
+            ) => lost.remove(sensor_id),
+            None => false,
+        };
+        let sensor = if lost.is_empty() { SensorState::Connected } else { SensorState::Lost };
+        let next = Status { sensor, network: network_state() };
+        if status != next {
+            status = next;
+            display.set_status(&status)?;
+        }
+
+        let Some(event) = event else { continue };
+        match event {
+            SensorEvent::Touched { .. } => display.wake()?,
+            SensorEvent::Matched { sensor_id, slot } => {
+                display.show(&Layout::new().centred(sensor_id).headline(format!("{}", slot)))?;
+            }
+            SensorEvent::Rejected { sensor_id } => {
+                display.draw(vec![&sensor_id, "Not Recognised"])?;
+            }
+            SensorEvent::Error { sensor_id, message, .. } => {
+                println!("[WARNING] Sensor {}: {}", sensor_id, message);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "simulator"))]
+mod tests {
+    use super::*;
+
+    // the simulator's finger sits on the last enrolled slot, past where searches used to stop
+    #[test]
+    fn emulated_finger_matches_above_slot_163() {
+        let mut sensor = FingerprintSensor::with_transport(open_emulator(300), 0xFFFFFFFF, 0).unwrap();
+        assert_eq!(sensor.search().unwrap(), Some(299));
+    }
+
+    #[test]
+    fn emulated_library_is_capped_at_its_capacity() {
+        let mut sensor = FingerprintSensor::with_transport(open_emulator(u16::MAX), 0xFFFFFFFF, 0).unwrap();
+        assert_eq!(sensor.search().unwrap(), Some(EMULATOR_CAPACITY - 1));
+    }
 }
@@ -0,0 +1,61 @@
+use crate::sensors::display::status::NetworkState;
+
+use std::fs;
+
+// RTF_UP in /proc/net/route's flags column
+const ROUTE_UP: u16 = 0x0001;
+
+// online while the kernel has a default route up through something other than loopback,
+// offline when it has none or the routing table can't be read, e.g. off Linux
+pub fn network_state() -> NetworkState {
+    match fs::read_to_string("/proc/net/route") {
+        Ok(routes) if has_default_route(&routes) => NetworkState::Online,
+        _ => NetworkState::Offline,
+    }
+}
+
+// one route per line after the header: interface, destination, gateway and flags in hex
+fn has_default_route(routes: &str) -> bool {
+    routes.lines().skip(1).any(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            [interface, "00000000", _, flags, ..] if *interface != "lo" => {
+                u16::from_str_radix(flags, 16).is_ok_and(|flags| flags & ROUTE_UP != 0)
+            }
+            _ => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "Iface\tDestination\tGateway\tFlags\tRefCnt\tUse\tMetric\tMask\tMTU\tWindow\tIRTT";
+
+    #[test]
+    fn a_default_route_that_is_up_is_online() {
+        let routes = format!(
+            "{}\neth0\t000200C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n\
+             wlan0\t00000000\t010200C0\t0003\t0\t0\t600\t00000000\t0\t0\t0\n",
+            HEADER
+        );
+        assert!(has_default_route(&routes));
+    }
+
+    #[test]
+    fn local_routes_alone_are_offline() {
+        let routes = format!("{}\neth0\t000200C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n", HEADER);
+        assert!(!has_default_route(&routes));
+        assert!(!has_default_route(HEADER));
+        assert!(!has_default_route(""));
+    }
+
+    #[test]
+    fn a_default_route_that_is_down_or_on_loopback_is_offline() {
+        let down = format!("{}\neth0\t00000000\t010200C0\t0002\t0\t0\t0\t00000000\t0\t0\t0\n", HEADER);
+        assert!(!has_default_route(&down));
+        let loopback = format!("{}\nlo\t00000000\t00000000\t0001\t0\t0\t0\t00000000\t0\t0\t0\n", HEADER);
+        assert!(!has_default_route(&loopback));
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+// host side record of who is enrolled in which sensor slot, one `slot<TAB>name` per line
+pub struct UserRegistry {
+    path: PathBuf,
+    users: BTreeMap<u16, String>,
+}
+
+// differences between the registry and the sensor's library found at startup
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    // templates on the sensor nobody in the registry owns
+    pub adopted: Vec<u16>,
+    // registered users whose template is no longer on the sensor, kept in the registry in
+    // case the sensor was swapped or a backup is yet to be restored
+    pub missing: Vec<u16>,
+}
+
+impl UserRegistry {
+    // a missing file is an empty registry, e.g. on first boot
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut users = BTreeMap::new();
+        for (line_no, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let parsed = line
+                .split_once('\t')
+                .and_then(|(slot, name)| Some((slot.trim().parse().ok()?, name.to_string())));
+            let (slot, name) = parsed.ok_or_else(|| {
+                io::Error::new(ErrorKind::InvalidData, format!("Bad registry line {}: {}", line_no + 1, line))
+            })?;
+            users.insert(slot, name);
+        }
+
+        Ok(UserRegistry { path, users })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let text: String = self
+            .users
+            .iter()
+            .map(|(slot, name)| format!("{}\t{}\n", slot, name))
+            .collect();
+
+        let temp = self.path.with_extension("tmp");
+        fs::write(&temp, text)?;
+        fs::rename(&temp, &self.path)
+    }
+
+    pub fn name(&self, slot: u16) -> Option<&str> {
+        self.users.get(&slot).map(String::as_str)
+    }
+
+    pub fn insert(&mut self, slot: u16, name: &str) {
+        self.users.insert(slot, name.to_string());
+    }
+
+    pub fn clear(&mut self) {
+        self.users.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    // lowest slot not taken by anyone, so new enrollments never overwrite a user
+    pub fn next_free_slot(&self, capacity: u16) -> Option<u16> {
+        (1..capacity).find(|slot| !self.users.contains_key(slot))
+    }
+
+    // registers templates on the sensor nobody owns and reports users whose template is gone,
+    // without removing them or touching the sensor itself
+    pub fn reconcile(&mut self, occupied: &[u16]) -> Reconciliation {
+        let mut result = Reconciliation::default();
+
+        for &slot in occupied {
+            if let Entry::Vacant(entry) = self.users.entry(slot) {
+                entry.insert(format!("Unknown {}", slot));
+                result.adopted.push(slot);
+            }
+        }
+        result.missing = self.missing(occupied);
+
+        result
+    }
+
+    // registered users whose slot isn't among the occupied ones
+    pub fn missing(&self, occupied: &[u16]) -> Vec<u16> {
+        self.users.keys().copied().filter(|slot| !occupied.contains(slot)).collect()
+    }
+
+    // drops users whose template is gone from the sensor, only ever on explicit request
+    pub fn prune(&mut self, occupied: &[u16]) -> Vec<u16> {
+        let missing = self.missing(occupied);
+        self.users.retain(|slot, _| !missing.contains(slot));
+        missing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("attendpi-registry-{}-{}.tsv", name, std::process::id()))
+    }
+
+    fn registry(users: &[(u16, &str)]) -> UserRegistry {
+        let mut registry = UserRegistry { path: temp_path("unsaved"), users: BTreeMap::new() };
+        for &(slot, name) in users {
+            registry.insert(slot, name);
+        }
+        registry
+    }
+
+    #[test]
+    fn reconcile_adopts_unknown_slots_and_keeps_missing_users() {
+        let mut registry = registry(&[(1, "Ada"), (2, "Grace"), (5, "Linus")]);
+        let result = registry.reconcile(&[1, 3, 5, 7]);
+
+        assert_eq!(result, Reconciliation { adopted: vec![3, 7], missing: vec![2] });
+        assert_eq!(registry.name(1), Some("Ada"));
+        assert_eq!(registry.name(2), Some("Grace"));
+        assert_eq!(registry.name(3), Some("Unknown 3"));
+        assert_eq!(registry.name(7), Some("Unknown 7"));
+        assert_eq!(registry.len(), 5);
+
+        // a second run has nothing new to adopt
+        assert_eq!(registry.reconcile(&[1, 3, 5, 7]), Reconciliation { adopted: vec![], missing: vec![2] });
+    }
+
+    #[test]
+    fn prune_drops_only_stale_users() {
+        let mut registry = registry(&[(1, "Ada"), (2, "Grace"), (5, "Linus"), (9, "Ken")]);
+        assert_eq!(registry.prune(&[1, 5]), [2, 9]);
+        assert_eq!(registry.name(1), Some("Ada"));
+        assert_eq!(registry.name(2), None);
+        assert_eq!(registry.name(9), None);
+        assert_eq!(registry.len(), 2);
+        assert!(registry.prune(&[1, 5]).is_empty());
+    }
+
+    #[test]
+    fn next_free_slot_skips_taken_slots_and_slot_zero() {
+        assert_eq!(registry(&[]).next_free_slot(10), Some(1));
+        assert_eq!(registry(&[(1, "Ada"), (2, "Grace"), (4, "Ken")]).next_free_slot(10), Some(3));
+        assert_eq!(registry(&[(1, "Ada"), (2, "Grace")]).next_free_slot(3), None);
+    }
+
+    #[test]
+    fn saved_registry_loads_back() {
+        let path = temp_path("round-trip");
+        let mut saved = UserRegistry::load(&path).unwrap();
+        assert!(saved.is_empty());
+        saved.insert(12, "Grace Hopper");
+        saved.insert(3, "Ada Lovelace");
+        saved.save().unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        let loaded = UserRegistry::load(&path);
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(text, "3\tAda Lovelace\n12\tGrace Hopper\n");
+        assert_eq!(loaded.unwrap().users, saved.users);
+    }
+
+    #[test]
+    fn load_skips_blank_lines_and_rejects_bad_ones() {
+        let path = temp_path("bad-line");
+        fs::write(&path, "1\tAda\n\n2 Grace\n").unwrap();
+        let error = UserRegistry::load(&path).err().unwrap();
+
+        fs::write(&path, "1\tAda\n\n 2\tGrace\n").unwrap();
+        let loaded = UserRegistry::load(&path);
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 3"), "{}", error);
+        assert_eq!(loaded.unwrap().name(2), Some("Grace"));
+    }
+}
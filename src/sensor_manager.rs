@@ -0,0 +1,240 @@
+use crate::sensors::r305_fingerprint_sensor::error::FingerprintError;
+use crate::sensors::r305_fingerprint_sensor::lib::FingerprintSensor;
+use crate::sensors::r305_fingerprint_sensor::sharding::TemplatePage;
+use crate::sensors::r305_fingerprint_sensor::transport::open_port;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const DEFAULT_BAUD_RATE: u32 = 57600;
+const DEFAULT_ADDRESS: u32 = 0xFFFFFFFF;
+const DEFAULT_PASSWORD: u32 = 0x00000000;
+
+// how long a worker waits before searching again after an error worth retrying, doubled
+// while they keep coming so a noisy or unplugged line isn't hammered
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(5);
+
+// one sensor, e.g. one entrance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SensorConfig {
+    pub id: String,
+    pub port: String,
+    pub baud_rate: u32,
+    pub address: u32,
+    pub password: u32,
+}
+
+impl SensorConfig {
+    // parses `id=port[,address[,password]]` entries separated by `;`,
+    // e.g. `front=/dev/ttyAMA0;back=/dev/ttyUSB0,0xFFFFFFFF,0x00000000`
+    pub fn parse_list(spec: &str) -> Result<Vec<SensorConfig>, String> {
+        spec.split(';')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    fn parse(entry: &str) -> Result<SensorConfig, String> {
+        let (id, rest) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Expected id=port in \"{}\"", entry))?;
+        let mut fields = rest.split(',').map(str::trim);
+
+        let port = fields.next().filter(|port| !port.is_empty());
+        let port = port.ok_or_else(|| format!("Missing port for sensor {}", id))?;
+        let address = fields.next().map(parse_u32).transpose()?.unwrap_or(DEFAULT_ADDRESS);
+        let password = fields.next().map(parse_u32).transpose()?.unwrap_or(DEFAULT_PASSWORD);
+
+        Ok(SensorConfig {
+            id: id.trim().to_string(),
+            port: port.to_string(),
+            baud_rate: DEFAULT_BAUD_RATE,
+            address,
+            password,
+        })
+    }
+}
+
+fn parse_u32(text: &str) -> Result<u32, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("Invalid number \"{}\"", text))
+}
+
+// what a worker saw, tagged with the sensor it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SensorEvent {
+    // a finger is on the sensor, sent before its search has finished
+    Touched { sensor_id: String },
+    Matched { sensor_id: String, slot: u16 },
+    Rejected { sensor_id: String },
+    // lost when the sensor stopped answering properly, or won't be polled again, rather than
+    // reporting a problem with the finger
+    Error { sensor_id: String, message: String, lost: bool },
+}
+
+// owns several sensors and polls each one on its own thread
+pub struct SensorManager {
+    running: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+    events: Receiver<SensorEvent>,
+}
+
+impl SensorManager {
+    // opens every configured sensor up front so a wiring mistake shows at startup. Each one
+    // searches every page when there are pages, with its own page loaded
+    pub fn start(configs: Vec<SensorConfig>, pages: Vec<TemplatePage>) -> Result<Self, FingerprintError> {
+        let mut sensors = Vec::new();
+        for config in configs {
+            let port = open_port(&config.port, config.baud_rate)?;
+            let mut sensor = FingerprintSensor::with_transport(port, config.address, config.password)?;
+            if !pages.is_empty() {
+                sensor.set_pages(pages.clone())?;
+            }
+            log(&format!("Sensor {} ready on {}", config.id, config.port), false);
+            sensors.push((config.id, sensor));
+        }
+
+        let running = Arc::new(AtomicBool::new(true));
+        let (sender, events) = mpsc::channel();
+
+        let workers = sensors
+            .into_iter()
+            .map(|(id, sensor)| {
+                let running = Arc::clone(&running);
+                let sender = sender.clone();
+                thread::spawn(move || poll(id, sensor, running, sender))
+            })
+            .collect();
+
+        Ok(SensorManager { running, workers, events })
+    }
+
+    pub fn events(&self) -> &Receiver<SensorEvent> {
+        &self.events
+    }
+
+    // workers finish their current search, or wait out their backoff, before exiting
+    pub fn stop(self) {
+        self.running.store(false, Ordering::SeqCst);
+        for worker in self.workers {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn poll(id: String, mut sensor: FingerprintSensor, running: Arc<AtomicBool>, events: Sender<SensorEvent>) {
+    let touched = events.clone();
+    let sensor_id = id.clone();
+    sensor.set_on_finger(move || {
+        let _ = touched.send(SensorEvent::Touched { sensor_id: sensor_id.clone() });
+    });
+
+    let mut backoff = RETRY_BACKOFF;
+    while running.load(Ordering::SeqCst) {
+        let mut fatal = false;
+        let mut failed = false;
+        let event = match sensor.search() {
+            Ok(Some(slot)) => SensorEvent::Matched { sensor_id: id.clone(), slot },
+            Ok(None) => SensorEvent::Rejected { sensor_id: id.clone() },
+            // nobody at this entrance
+            Err(FingerprintError::MaxRetries) => continue,
+            Err(e) => {
+                fatal = !e.is_retryable();
+                failed = true;
+                let lost = fatal || e.is_link_error();
+                SensorEvent::Error { sensor_id: id.clone(), message: e.to_string(), lost }
+            }
+        };
+
+        if events.send(event).is_err() || fatal {
+            log(&format!("Sensor {} stopped polling", id), fatal);
+            break;
+        }
+
+        if failed {
+            thread::sleep(backoff);
+            backoff = next_backoff(backoff);
+        } else {
+            backoff = RETRY_BACKOFF;
+        }
+    }
+}
+
+fn next_backoff(backoff: Duration) -> Duration {
+    (backoff * 2).min(MAX_RETRY_BACKOFF)
+}
+
+fn log(message: &str, warning: bool) {
+    println!("{} SensorManager: {}", if warning { "[WARNING]" } else { "[INFO]" }, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sensors_with_default_address_and_password() {
+        let configs = SensorConfig::parse_list("front=/dev/ttyAMA0; back = /dev/ttyUSB0 ;").unwrap();
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[0].id, "front");
+        assert_eq!(configs[0].port, "/dev/ttyAMA0");
+        assert_eq!(configs[0].baud_rate, DEFAULT_BAUD_RATE);
+        assert_eq!(configs[0].address, DEFAULT_ADDRESS);
+        assert_eq!(configs[0].password, DEFAULT_PASSWORD);
+        assert_eq!(configs[1].id, "back");
+        assert_eq!(configs[1].port, "/dev/ttyUSB0");
+    }
+
+    #[test]
+    fn parses_hex_and_decimal_address_and_password() {
+        let configs = SensorConfig::parse_list("side=/dev/ttyUSB1,0x0000ABCD,1234").unwrap();
+        assert_eq!(configs[0].address, 0xABCD);
+        assert_eq!(configs[0].password, 1234);
+    }
+
+    #[test]
+    fn empty_list_has_no_sensors() {
+        assert!(SensorConfig::parse_list("").unwrap().is_empty());
+        assert!(SensorConfig::parse_list(" ; ;").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        // no `=`, no port, and numbers that don't parse
+        assert!(SensorConfig::parse_list("/dev/ttyAMA0").is_err());
+        assert!(SensorConfig::parse_list("front=").is_err());
+        assert!(SensorConfig::parse_list("front=,0xFFFFFFFF").is_err());
+        assert!(SensorConfig::parse_list("front=/dev/ttyAMA0,0xZZ").is_err());
+        assert!(SensorConfig::parse_list("front=/dev/ttyAMA0,0x1,password").is_err());
+        // one bad entry fails the whole list rather than dropping a sensor
+        assert!(SensorConfig::parse_list("front=/dev/ttyAMA0;back").is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let mut backoff = RETRY_BACKOFF;
+        let mut seen = vec![backoff];
+        while backoff < MAX_RETRY_BACKOFF {
+            backoff = next_backoff(backoff);
+            seen.push(backoff);
+        }
+        assert_eq!(seen[1], RETRY_BACKOFF * 2);
+        assert_eq!(seen[2], RETRY_BACKOFF * 4);
+        assert_eq!(*seen.last().unwrap(), MAX_RETRY_BACKOFF);
+        assert_eq!(next_backoff(MAX_RETRY_BACKOFF), MAX_RETRY_BACKOFF);
+    }
+
+    #[test]
+    fn rejects_baud_rates_the_sensor_cannot_run_at() {
+        let error = open_port("/dev/null", 115200).err().unwrap();
+        assert!(matches!(&error, FingerprintError::Serial(e) if e.kind() == std::io::ErrorKind::InvalidInput));
+    }
+}
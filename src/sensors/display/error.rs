@@ -0,0 +1,16 @@
+use std::io;
+use thiserror::Error;
+
+// display specific errors
+#[derive(Error, Debug)]
+pub enum DisplayError {
+    #[error("Display I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[cfg(feature = "i2c-display")]
+    #[error("I2C error: {0}")]
+    I2c(#[from] linux_embedded_hal::I2CError),
+
+    #[error("Render thread has stopped")]
+    Stopped,
+}
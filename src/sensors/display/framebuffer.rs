@@ -0,0 +1,216 @@
+use crate::sensors::display::error::DisplayError;
+use crate::sensors::display::layout::Layout;
+use crate::sensors::display::status::{draw_screen, Status};
+use crate::sensors::display::Display;
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+use std::convert::Infallible;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub const WIDTH: u32 = 128;
+pub const HEIGHT: u32 = 64;
+
+// a monochrome screen kept in memory, e.g. for snapshots of UI screens or a panel's frame
+pub struct FrameBuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<bool>,
+    // every drawn screen is written here as a numbered PNG when set
+    snapshot_dir: Option<PathBuf>,
+    snapshots: usize,
+    // drawn above every screen shown through Display once set
+    status: Option<Status>,
+}
+
+impl FrameBuffer {
+    // 128x64
+    pub fn new() -> Self {
+        Self::with_size(WIDTH, HEIGHT)
+    }
+
+    pub fn with_size(width: u32, height: u32) -> Self {
+        FrameBuffer {
+            width,
+            height,
+            pixels: vec![false; (width * height) as usize],
+            snapshot_dir: None,
+            snapshots: 0,
+            status: None,
+        }
+    }
+
+    // saves every screen drawn through Display as dir/NNNN.png from now on
+    pub fn with_snapshots(mut self, dir: impl AsRef<Path>) -> io::Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        self.snapshot_dir = Some(dir.as_ref().to_path_buf());
+        self.log(&format!("Saving screens to {}", dir.as_ref().display()), false);
+        Ok(self)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height && self.pixels[(y * self.width + x) as usize]
+    }
+
+    // 8 bit greyscale PNG, lit pixels white like on the OLED
+    pub fn write_png(&self, writer: impl Write) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let data: Vec<u8> = self.pixels.iter().map(|&on| if on { 0xFF } else { 0x00 }).collect();
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&data))
+            .map_err(io::Error::other)
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write_png(&mut file)?;
+        file.flush()
+    }
+
+    fn snapshot(&mut self) -> io::Result<()> {
+        let Some(dir) = &self.snapshot_dir else {
+            return Ok(());
+        };
+        let path = dir.join(format!("{:04}.png", self.snapshots));
+        self.snapshots += 1;
+
+        self.save_png(&path)
+            .map_err(|e| io::Error::new(e.kind(), format!("Could not save {}: {}", path.display(), e)))
+    }
+
+    fn log(&self, message: &str, warning: bool) {
+        println!("{} FrameBuffer: {}", if warning { "[WARNING]" } else { "[INFO]" }, message);
+    }
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, colour) in pixels {
+            if let (Ok(x), Ok(y)) = (u32::try_from(point.x), u32::try_from(point.y))
+                && x < self.width
+                && y < self.height
+            {
+                self.pixels[(y * self.width + x) as usize] = colour.is_on();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for FrameBuffer {
+    // only the first frame of a screen is saved, not every step of its animation
+    fn render(&mut self, layout: &Layout, tick: u32) -> Result<bool, DisplayError> {
+        let status = self.status;
+        let Ok(animated) = draw_screen(self, layout, status.as_ref(), tick);
+        if tick == 0 {
+            self.snapshot()?;
+        }
+        Ok(animated)
+    }
+
+    fn set_status(&mut self, status: &Status) -> Result<(), DisplayError> {
+        self.status = Some(*status);
+        Ok(())
+    }
+
+    fn cleanup(&mut self) -> Result<(), DisplayError> {
+        let Ok(()) = self.clear(BinaryColor::Off);
+        Ok(self.snapshot()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::display::status::BAR_HEIGHT;
+
+    // left, top, right and bottom of the lit pixels, None when nothing is lit
+    fn lit_bounds(frame: &FrameBuffer) -> Option<(u32, u32, u32, u32)> {
+        let lit = (0..frame.height()).flat_map(|y| (0..frame.width()).map(move |x| (x, y)));
+        lit.filter(|&(x, y)| frame.pixel(x, y)).fold(None, |bounds, (x, y)| match bounds {
+            None => Some((x, y, x, y)),
+            Some((left, top, right, bottom)) => Some((left.min(x), top.min(y), right.max(x), bottom.max(y))),
+        })
+    }
+
+    #[test]
+    fn empty_layout_leaves_the_frame_blank() {
+        let mut frame = FrameBuffer::with_size(128, 32);
+        frame.render(&Layout::new(), 0).unwrap();
+        assert_eq!(lit_bounds(&frame), None);
+    }
+
+    #[test]
+    fn centred_line_is_drawn_in_the_middle() {
+        let mut frame = FrameBuffer::with_size(128, 32);
+        frame.render(&Layout::new().centred("Hello"), 0).unwrap();
+
+        let (left, top, right, bottom) = lit_bounds(&frame).unwrap();
+        assert!(left.abs_diff(127 - right) <= 2, "{} left of the text, {} right of it", left, 127 - right);
+        assert!(top.abs_diff(31 - bottom) <= 2, "{} above the text, {} below it", top, 31 - bottom);
+    }
+
+    #[test]
+    fn status_bar_keeps_the_layout_below_it() {
+        let mut frame = FrameBuffer::with_size(128, 32);
+        frame.set_status(&Status::default()).unwrap();
+        frame.render(&Layout::new().centred("Hello"), 0).unwrap();
+
+        // the underline runs the whole width with a blank row under it
+        let underline = BAR_HEIGHT - 2;
+        assert!((0..128).all(|x| frame.pixel(x, underline)));
+        assert!((0..128).all(|x| !frame.pixel(x, underline + 1)));
+        assert!((BAR_HEIGHT..32).any(|y| (0..128).any(|x| frame.pixel(x, y))));
+    }
+
+    #[test]
+    fn png_matches_the_frame() {
+        let mut frame = FrameBuffer::with_size(128, 32);
+        frame.render(&Layout::new().headline("Welcome"), 0).unwrap();
+
+        let mut png = Vec::new();
+        frame.write_png(&mut png).unwrap();
+
+        let mut reader = png::Decoder::new(io::Cursor::new(png)).read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        assert_eq!((info.width, info.height), (128, 32));
+        for y in 0..32 {
+            for x in 0..128 {
+                let expected = if frame.pixel(x, y) { 0xFF } else { 0x00 };
+                assert_eq!(data[(y * 128 + x) as usize], expected, "pixel {},{}", x, y);
+            }
+        }
+    }
+}
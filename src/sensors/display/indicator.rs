@@ -0,0 +1,109 @@
+use embedded_graphics::{
+    image::{Image, ImageRaw},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Circle, PrimitiveStyle, Rectangle},
+};
+
+// indicators drawn beside text are this many pixels square
+pub const SIDE: u32 = 16;
+
+// rows taken by a progress bar, and the blank row above it
+pub const BAR_HEIGHT: u32 = 7;
+
+// where the spinner's 8 dots sit around its centre, clockwise from the top
+const SPINNER_DOTS: [(i32, i32); 8] = [(0, -6), (4, -4), (6, 0), (4, 4), (0, 6), (-4, 4), (-6, 0), (-4, -4)];
+
+// 16x16 bitmaps shown one after the other, two bytes per row with the leftmost pixel in the
+// top bit, each for `ticks` render ticks
+#[derive(Debug, PartialEq, Eq)]
+pub struct Animation {
+    pub frames: &'static [[u8; 32]],
+    pub ticks: u32,
+}
+
+// ridges of a fingerprint appearing one by one, for while the sensor waits for a finger
+pub static FINGER: Animation = Animation {
+    frames: &[
+        [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x03, 0xC0,
+            0x02, 0x40, 0x02, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ],
+        [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xC0, 0x06, 0x60, 0x0C, 0xB0, 0x0B, 0xD0,
+            0x0A, 0x50, 0x0A, 0x50, 0x08, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ],
+        [
+            0x00, 0x00, 0x00, 0x00, 0x07, 0xE0, 0x0C, 0x30, 0x13, 0xC8, 0x36, 0x6C, 0x2C, 0xB4, 0x2B, 0xD4,
+            0x2A, 0x54, 0x2A, 0x54, 0x28, 0x14, 0x20, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ],
+    ],
+    ticks: 4,
+};
+
+// what is drawn to the left of a screen's text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indicator {
+    // a ring of dots going round, one step per tick
+    Spinner,
+    Animation(&'static Animation),
+}
+
+pub fn draw_indicator<D>(target: &mut D, indicator: Indicator, top_left: Point, tick: u32) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    match indicator {
+        Indicator::Spinner => draw_spinner(target, top_left, tick),
+        Indicator::Animation(animation) => {
+            let frame = (tick / animation.ticks.max(1)) as usize % animation.frames.len().max(1);
+            let Some(bitmap) = animation.frames.get(frame) else {
+                return Ok(());
+            };
+            let raw = ImageRaw::<BinaryColor>::new(bitmap, SIDE);
+            Image::new(&raw, top_left).draw(target)
+        }
+    }
+}
+
+// the dot the spinner is on and the one behind it are big, the rest small
+fn draw_spinner<D>(target: &mut D, top_left: Point, tick: u32) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let centre = top_left + Point::new(SIDE as i32 / 2, SIDE as i32 / 2);
+    let head = tick as usize % SPINNER_DOTS.len();
+    let tail = (head + SPINNER_DOTS.len() - 1) % SPINNER_DOTS.len();
+
+    for (index, &(x, y)) in SPINNER_DOTS.iter().enumerate() {
+        let diameter = if index == head || index == tail { 3 } else { 1 };
+        Circle::with_center(centre + Point::new(x, y), diameter)
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(target)?;
+    }
+    Ok(())
+}
+
+// an outline along the bottom of the area filled in proportion to done out of total
+pub fn draw_progress<D>(target: &mut D, area: Rectangle, done: u32, total: u32) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let height = BAR_HEIGHT - 1;
+    let outline = Rectangle::new(
+        Point::new(area.top_left.x, area.top_left.y + area.size.height.saturating_sub(height) as i32),
+        Size::new(area.size.width, height),
+    );
+    outline
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(target)?;
+
+    let inner = outline.size.width.saturating_sub(4);
+    let filled = match total {
+        0 => inner,
+        _ => inner * done.min(total) / total,
+    };
+    Rectangle::new(outline.top_left + Point::new(2, 2), Size::new(filled, height.saturating_sub(4)))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(target)
+}
@@ -0,0 +1,668 @@
+use crate::sensors::display::indicator::{self, draw_indicator, draw_progress, Animation, Indicator};
+use crate::sensors::display::qr::{self, draw_qr, encode};
+
+use embedded_graphics::{
+    mono_font::{
+        ascii::{FONT_10X20, FONT_4X6, FONT_5X8, FONT_6X10, FONT_8X13},
+        MonoFont, MonoTextStyle,
+    },
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle, Triangle},
+    text::{Baseline, Text},
+};
+
+// blank columns kept on either side of the text, in panel pixels
+const MARGIN: u32 = 5;
+
+// what overflowing text is cut down to, the ASCII fonts have no '…'
+const ELLIPSIS: &str = "...";
+
+// animation speeds in render ticks (see renderer::TICK): a marquee waits at its start,
+// then moves a few pixels per tick with a gap before the text comes round again
+const SCROLL_HOLD_TICKS: u32 = 10;
+const SCROLL_STEP: u32 = 2;
+const SCROLL_GAP: u32 = 24;
+const PAGE_TICKS: u32 = 30;
+
+// ticks between each pixel a drifting screen moves
+const DRIFT_TICKS: u32 = 20;
+
+// font sizes tried from largest to smallest until everything fits, a headline
+// always stays a step bigger than the body text next to it
+static BODY_FONTS: [&MonoFont<'static>; 3] = [&FONT_6X10, &FONT_5X8, &FONT_4X6];
+static HEADLINE_FONTS: [&MonoFont<'static>; 3] = [&FONT_10X20, &FONT_8X13, &FONT_6X10];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Centre,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Emphasis {
+    #[default]
+    Body,
+    // large text, e.g. the name on check-in
+    Headline,
+}
+
+// what a line does when it is wider than the screen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    #[default]
+    Wrap,
+    // stays on one row and scrolls sideways like a marquee
+    Scroll,
+}
+
+// one paragraph of a screen, wrapped onto as many rows as it needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub text: String,
+    pub align: Align,
+    pub emphasis: Emphasis,
+    pub overflow: Overflow,
+}
+
+impl Line {
+    pub fn new(text: impl Into<String>) -> Self {
+        Line {
+            text: text.into(),
+            align: Align::Left,
+            emphasis: Emphasis::Body,
+            overflow: Overflow::Wrap,
+        }
+    }
+
+    // centred large text on one row, scrolling when it is too long, e.g. a name
+    pub fn headline(text: impl Into<String>) -> Self {
+        Line::new(text)
+            .align(Align::Centre)
+            .emphasis(Emphasis::Headline)
+            .overflow(Overflow::Scroll)
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn emphasis(mut self, emphasis: Emphasis) -> Self {
+        self.emphasis = emphasis;
+        self
+    }
+
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+}
+
+impl From<&str> for Line {
+    fn from(text: &str) -> Self {
+        Line::new(text)
+    }
+}
+
+impl From<String> for Line {
+    fn from(text: String) -> Self {
+        Line::new(text)
+    }
+}
+
+// a whole screen of text, laid out to fit whatever size the target is
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Layout {
+    lines: Vec<Line>,
+    // flips through pages of rows instead of cutting off what doesn't fit
+    paged: bool,
+    // shows the lines as a list with this one highlighted, see menu
+    selected: Option<usize>,
+    // a QR code of this on the left, the lines beside it
+    qr: Option<String>,
+    // a spinner or animation on the left, the lines beside it
+    indicator: Option<Indicator>,
+    // a bar along the bottom, done out of total
+    progress: Option<(u32, u32)>,
+    // the lines move round the whole target, e.g. for a screensaver
+    drifting: bool,
+}
+
+impl Layout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn line(mut self, line: impl Into<Line>) -> Self {
+        self.lines.push(line.into());
+        self
+    }
+
+    pub fn centred(self, text: impl Into<String>) -> Self {
+        self.line(Line::new(text).align(Align::Centre))
+    }
+
+    pub fn headline(self, text: impl Into<String>) -> Self {
+        self.line(Line::headline(text))
+    }
+
+    // for lists, e.g. who is in today
+    pub fn paged(mut self) -> Self {
+        self.paged = true;
+        self
+    }
+
+    // one row per line, scrolled so the selected line is in view and drawn inverted
+    pub fn select(mut self, index: usize) -> Self {
+        self.selected = Some(index);
+        self
+    }
+
+    // e.g. a pairing URL during setup or a receipt after check-in
+    pub fn qr(mut self, data: impl Into<String>) -> Self {
+        self.qr = Some(data.into());
+        self
+    }
+
+    // e.g. while the sensor merges templates
+    pub fn spinner(mut self) -> Self {
+        self.indicator = Some(Indicator::Spinner);
+        self
+    }
+
+    pub fn animation(mut self, animation: &'static Animation) -> Self {
+        self.indicator = Some(Indicator::Animation(animation));
+        self
+    }
+
+    pub fn progress(mut self, done: u32, total: u32) -> Self {
+        self.progress = Some((done, total));
+        self
+    }
+
+    pub fn drifting(mut self) -> Self {
+        self.drifting = true;
+        self
+    }
+
+    pub fn is_drifting(&self) -> bool {
+        self.drifting
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn qr_data(&self) -> Option<&str> {
+        self.qr.as_deref()
+    }
+}
+
+impl From<Vec<&str>> for Layout {
+    fn from(data: Vec<&str>) -> Self {
+        Layout {
+            lines: data.into_iter().map(Line::from).collect(),
+            paged: false,
+            selected: None,
+            qr: None,
+            indicator: None,
+            progress: None,
+            drifting: false,
+        }
+    }
+}
+
+// one wrapped row, ready to draw
+struct Row {
+    text: String,
+    font: &'static MonoFont<'static>,
+    align: Align,
+    scroll: bool,
+}
+
+impl Row {
+    fn width(&self) -> u32 {
+        text_width(self.font, self.text.chars().count())
+    }
+}
+
+// draws one frame of the layout vertically centred in the target, at the largest font size
+// the whole layout fits at. `tick` moves marquees along and flips pages, the result is
+// whether a later tick would draw anything different
+pub fn draw_layout<D>(target: &mut D, layout: &Layout, tick: u32) -> Result<bool, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    target.clear(BinaryColor::Off)?;
+    if let Some(data) = &layout.qr {
+        return draw_beside_qr(target, layout, data, tick);
+    }
+    if let Some(selected) = layout.selected {
+        return draw_list(target, layout, selected, tick);
+    }
+    if layout.drifting {
+        return draw_drifting(target, layout, tick);
+    }
+
+    let mut area = target.bounding_box();
+    let mut animated = false;
+
+    if let Some((done, total)) = layout.progress {
+        let bar = Rectangle::new(
+            area.top_left + Point::new(MARGIN as i32, 0),
+            Size::new(area.size.width.saturating_sub(2 * MARGIN), area.size.height),
+        );
+        draw_progress(target, bar, done, total)?;
+        area.size.height = area.size.height.saturating_sub(indicator::BAR_HEIGHT);
+    }
+
+    if let Some(shown) = layout.indicator {
+        let top_left = area.top_left + Point::new(MARGIN as i32, (area.size.height as i32 - indicator::SIDE as i32) / 2);
+        draw_indicator(target, shown, top_left, tick)?;
+
+        let used = MARGIN + indicator::SIDE;
+        area = Rectangle::new(
+            area.top_left + Point::new(used as i32, 0),
+            Size::new(area.size.width.saturating_sub(used), area.size.height),
+        );
+        animated = true;
+    }
+
+    Ok(draw_rows(&mut target.cropped(&area), layout, tick)? || animated)
+}
+
+// the lines wrapped, scaled and centred on an already cleared target
+fn draw_rows<D>(target: &mut D, layout: &Layout, tick: u32) -> Result<bool, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let area = target.bounding_box();
+    let width = area.size.width.saturating_sub(2 * MARGIN);
+    let pages = fit(layout, width, area.size.height);
+    let rows = &pages[(tick / PAGE_TICKS) as usize % pages.len()];
+    let mut animated = pages.len() > 1;
+
+    let height: u32 = rows.iter().map(|row| row.font.character_size.height).sum();
+    let mut y = area.top_left.y + (area.size.height as i32 - height as i32) / 2;
+
+    for row in rows {
+        let style = MonoTextStyle::new(row.font, BinaryColor::On);
+        let row_width = row.width();
+
+        if row.scroll && row_width > width {
+            let bounds = Rectangle::new(
+                Point::new(area.top_left.x + MARGIN as i32, y),
+                Size::new(width, row.font.character_size.height),
+            );
+            draw_marquee(target, &row.text, style, bounds, tick)?;
+            animated = true;
+        } else {
+            let x = match row.align {
+                Align::Left => MARGIN as i32,
+                Align::Centre => (area.size.width as i32 - row_width as i32) / 2,
+                Align::Right => area.size.width.saturating_sub(MARGIN) as i32 - row_width as i32,
+            };
+            Text::with_baseline(&row.text, Point::new(area.top_left.x + x, y), style, Baseline::Top)
+                .draw(target)?;
+        }
+
+        y += row.font.character_size.height as i32;
+    }
+
+    Ok(animated)
+}
+
+// the lines in a box just big enough for them, moved a pixel every DRIFT_TICKS and bounced
+// off the edges of the target
+fn draw_drifting<D>(target: &mut D, layout: &Layout, tick: u32) -> Result<bool, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    if layout.lines.is_empty() {
+        return Ok(false);
+    }
+
+    let area = target.bounding_box();
+    let pages = fit(layout, area.size.width.saturating_sub(2 * MARGIN), area.size.height);
+    let Some(rows) = pages.first() else {
+        return Ok(false);
+    };
+    let width = rows.iter().map(Row::width).max().unwrap_or(0) + 2 * MARGIN;
+    let height: u32 = rows.iter().map(|row| row.font.character_size.height).sum();
+
+    let step = tick / DRIFT_TICKS;
+    let offset = Point::new(
+        bounce(step, area.size.width.saturating_sub(width)) as i32,
+        bounce(step, area.size.height.saturating_sub(height)) as i32,
+    );
+    let drifted = Rectangle::new(area.top_left + offset, Size::new(width, height));
+    draw_rows(&mut target.cropped(&drifted), layout, 0)?;
+    Ok(true)
+}
+
+// 0 up to span and back down again, one step at a time
+fn bounce(step: u32, span: u32) -> u32 {
+    if span == 0 {
+        return 0;
+    }
+    let position = step % (2 * span);
+    if position <= span { position } else { 2 * span - position }
+}
+
+// the code as big as the height allows, the lines in what is left to its right. Data too
+// long for a QR code is shown as a scrolling line instead so it can still be typed in
+fn draw_beside_qr<D>(target: &mut D, layout: &Layout, data: &str, tick: u32) -> Result<bool, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let text = Layout { qr: None, ..layout.clone() };
+    let area = target.bounding_box();
+    // too long to encode, or too many modules to fit the panel even at one pixel each
+    let Some(code) = encode(data).filter(|code| qr::side(code, area.size.height) <= area.size.height) else {
+        let text = text.line(Line::new(data).overflow(Overflow::Scroll));
+        return draw_layout(target, &text, tick);
+    };
+
+    let side = draw_qr(target, &code, area)?;
+    let beside = Rectangle::new(
+        area.top_left + Point::new(side as i32, 0),
+        Size::new(area.size.width.saturating_sub(side), area.size.height),
+    );
+    draw_rows(&mut target.cropped(&beside), &text, tick)
+}
+
+// a single row per line at the body size, a window of them around the selected one with
+// arrows in the right margin when there is more above or below
+fn draw_list<D>(target: &mut D, layout: &Layout, selected: usize, tick: u32) -> Result<bool, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let font = BODY_FONTS[0];
+    let row_height = font.character_size.height;
+    let area = target.bounding_box();
+    let width = area.size.width.saturating_sub(2 * MARGIN);
+
+    let count = layout.lines.len();
+    let selected = selected.min(count.saturating_sub(1));
+    let visible = ((area.size.height / row_height) as usize).clamp(1, count.max(1));
+    let first = selected.saturating_sub(visible / 2).min(count - visible.min(count));
+
+    let mut y = area.top_left.y + (area.size.height as i32 - (visible as u32 * row_height) as i32) / 2;
+    let mut animated = false;
+
+    for (index, line) in layout.lines.iter().enumerate().skip(first).take(visible) {
+        let bounds = Rectangle::new(Point::new(area.top_left.x + MARGIN as i32, y), Size::new(width, row_height));
+        let text = line.text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        let colour = if index == selected {
+            let highlight = Size::new(area.size.width.saturating_sub(MARGIN + 1), row_height);
+            Rectangle::new(Point::new(area.top_left.x + 1, y), highlight)
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                .draw(target)?;
+            BinaryColor::Off
+        } else {
+            BinaryColor::On
+        };
+        let style = MonoTextStyle::new(font, colour);
+
+        if index == selected && text_width(font, text.chars().count()) > width {
+            draw_marquee(target, &text, style, bounds, tick)?;
+            animated = true;
+        } else {
+            let text = match text.chars().count() > columns(font, width) {
+                true => ellipsize(&text, columns(font, width)),
+                false => text,
+            };
+            Text::with_baseline(&text, bounds.top_left, style, Baseline::Top).draw(target)?;
+        }
+
+        y += row_height as i32;
+    }
+
+    // small triangles pointing up and down, centred in the right margin
+    let arrow_x = area.top_left.x + area.size.width.saturating_sub(MARGIN) as i32;
+    let fill = PrimitiveStyle::with_fill(BinaryColor::On);
+    if first > 0 {
+        let top = area.top_left.y + 1;
+        Triangle::new(
+            Point::new(arrow_x, top + 3),
+            Point::new(arrow_x + 4, top + 3),
+            Point::new(arrow_x + 2, top + 1),
+        )
+        .into_styled(fill)
+        .draw(target)?;
+    }
+    if first + visible < count {
+        let bottom = area.top_left.y + area.size.height as i32 - 2;
+        Triangle::new(
+            Point::new(arrow_x, bottom - 2),
+            Point::new(arrow_x + 4, bottom - 2),
+            Point::new(arrow_x + 2, bottom),
+        )
+        .into_styled(fill)
+        .draw(target)?;
+    }
+
+    Ok(animated)
+}
+
+// two copies of the text a cycle apart, clipped to the bounds, so it loops round
+fn draw_marquee<D>(
+    target: &mut D,
+    text: &str,
+    style: MonoTextStyle<'static, BinaryColor>,
+    bounds: Rectangle,
+    tick: u32,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let cycle = text_width(style.font, text.chars().count()) + SCROLL_GAP;
+    let x = bounds.top_left.x - marquee_offset(tick, cycle) as i32;
+    let mut clipped = target.clipped(&bounds);
+    for x in [x, x + cycle as i32] {
+        Text::with_baseline(text, Point::new(x, bounds.top_left.y), style, Baseline::Top).draw(&mut clipped)?;
+    }
+    Ok(())
+}
+
+// how far a marquee has moved after `tick`, holding still at the start of every cycle
+fn marquee_offset(tick: u32, cycle: u32) -> u32 {
+    let phase = tick % (SCROLL_HOLD_TICKS + cycle.div_ceil(SCROLL_STEP));
+    (phase.saturating_sub(SCROLL_HOLD_TICKS) * SCROLL_STEP).min(cycle)
+}
+
+// the largest size where everything fits without scrolling, then the largest one that fits
+// vertically, otherwise pages of rows or the smallest size cut off with an ellipsis
+fn fit(layout: &Layout, width: u32, height: u32) -> Vec<Vec<Row>> {
+    let tall = |rows: &[Row]| rows.iter().map(|row| row.font.character_size.height).sum::<u32>();
+
+    for size in 0..BODY_FONTS.len() {
+        let rows = wrap_all(layout, size, width);
+        if tall(&rows) <= height && rows.iter().all(|row| !row.scroll || row.width() <= width) {
+            return vec![rows];
+        }
+    }
+    for size in 0..BODY_FONTS.len() {
+        let rows = wrap_all(layout, size, width);
+        if tall(&rows) <= height {
+            return vec![rows];
+        }
+    }
+
+    if layout.paged {
+        return paginate(wrap_all(layout, 0, width), height);
+    }
+
+    let mut rows = wrap_all(layout, BODY_FONTS.len() - 1, width);
+    let mut used = 0;
+    let visible = rows
+        .iter()
+        .take_while(|row| {
+            used += row.font.character_size.height;
+            used <= height
+        })
+        .count();
+
+    if visible < rows.len() {
+        rows.truncate(visible);
+        if let Some(last) = rows.last_mut() {
+            last.text = ellipsize(&last.text, columns(last.font, width));
+        }
+    }
+    vec![rows]
+}
+
+// as many rows per page as fit, at least one so nothing is lost
+fn paginate(rows: Vec<Row>, height: u32) -> Vec<Vec<Row>> {
+    let mut pages: Vec<Vec<Row>> = Vec::new();
+    let mut used = 0;
+
+    for row in rows {
+        let row_height = row.font.character_size.height;
+        match pages.last_mut() {
+            Some(page) if used + row_height <= height => page.push(row),
+            _ => {
+                pages.push(vec![row]);
+                used = 0;
+            }
+        }
+        used += row_height;
+    }
+    pages
+}
+
+fn wrap_all(layout: &Layout, size: usize, width: u32) -> Vec<Row> {
+    layout
+        .lines
+        .iter()
+        .flat_map(|line| {
+            let font = match line.emphasis {
+                Emphasis::Body => BODY_FONTS[size],
+                Emphasis::Headline => HEADLINE_FONTS[size],
+            };
+            let scroll = line.overflow == Overflow::Scroll;
+            let rows = match scroll {
+                true => vec![line.text.split_whitespace().collect::<Vec<_>>().join(" ")],
+                false => wrap(&line.text, columns(font, width)),
+            };
+            rows.into_iter().map(move |text| Row { text, font, align: line.align, scroll })
+        })
+        .collect()
+}
+
+// greedy word wrap, words longer than a row are split across rows
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut rows = Vec::new();
+    let mut row = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+
+        if !row.is_empty() && row.chars().count() + 1 + word.len() <= columns {
+            row.push(' ');
+            row.extend(&word);
+            continue;
+        }
+        if !row.is_empty() {
+            rows.push(std::mem::take(&mut row));
+        }
+
+        while word.len() > columns.max(1) {
+            rows.push(word.drain(..columns.max(1)).collect());
+        }
+        row.extend(&word);
+    }
+
+    if !row.is_empty() || rows.is_empty() {
+        rows.push(row);
+    }
+    rows
+}
+
+fn ellipsize(text: &str, columns: usize) -> String {
+    let keep = columns.saturating_sub(ELLIPSIS.len()).min(text.chars().count());
+    let mut text: String = text.chars().take(keep).collect();
+    text.truncate(text.trim_end().len());
+    text.push_str(ELLIPSIS);
+    text
+}
+
+fn columns(font: &MonoFont, width: u32) -> usize {
+    let pitch = font.character_size.width + font.character_spacing;
+    ((width + font.character_spacing) / pitch) as usize
+}
+
+fn text_width(font: &MonoFont, characters: usize) -> u32 {
+    let characters = characters as u32;
+    (characters * (font.character_size.width + font.character_spacing)).saturating_sub(font.character_spacing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::display::framebuffer::FrameBuffer;
+
+    #[test]
+    fn wrap_fills_rows_word_by_word() {
+        assert_eq!(wrap("the quick brown fox", 10), ["the quick", "brown fox"]);
+        assert_eq!(wrap("  spaced   out  ", 20), ["spaced out"]);
+        assert_eq!(wrap("", 10), [""]);
+    }
+
+    #[test]
+    fn wrap_splits_words_longer_than_a_row() {
+        assert_eq!(wrap("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("hi abcdefgh", 4), ["hi", "abcd", "efgh"]);
+        assert_eq!(wrap("abc", 0), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn ellipsize_fits_the_ellipsis_in_the_columns() {
+        assert_eq!(ellipsize("Hello world", 8), "Hello...");
+        // no space left before the ellipsis
+        assert_eq!(ellipsize("Hi there", 6), "Hi...");
+        assert_eq!(ellipsize("Hello", 2), "...");
+    }
+
+    #[test]
+    fn marquee_holds_then_scrolls_one_cycle() {
+        let cycle = 20;
+        assert_eq!(marquee_offset(0, cycle), 0);
+        assert_eq!(marquee_offset(SCROLL_HOLD_TICKS, cycle), 0);
+        assert_eq!(marquee_offset(SCROLL_HOLD_TICKS + 1, cycle), SCROLL_STEP);
+        assert_eq!(marquee_offset(SCROLL_HOLD_TICKS + 9, cycle), 9 * SCROLL_STEP);
+        // then back to the start, held again
+        assert_eq!(marquee_offset(SCROLL_HOLD_TICKS + cycle / SCROLL_STEP, cycle), 0);
+    }
+
+    #[test]
+    fn marquee_never_moves_past_its_cycle() {
+        let cycle = 7;
+        assert!((0..200).all(|tick| marquee_offset(tick, cycle) <= cycle));
+    }
+
+    #[test]
+    fn targets_narrower_than_the_margin_draw_without_overflowing() {
+        for width in [0, 1, MARGIN, MARGIN + 1] {
+            let mut frame = FrameBuffer::with_size(width, 32);
+            let list = Layout::new().line("Enroll").line("Backup").line("Restore").line("Wipe").line("Exit");
+            draw_layout(&mut frame, &list.select(2), 0).unwrap();
+            draw_layout(&mut frame, &Layout::new().line(Line::new("Right").align(Align::Right)), 0).unwrap();
+        }
+    }
+
+    #[test]
+    fn bounce_goes_up_to_the_span_and_back() {
+        let steps: Vec<u32> = (0..9).map(|step| bounce(step, 3)).collect();
+        assert_eq!(steps, [0, 1, 2, 3, 2, 1, 0, 1, 2]);
+        assert_eq!(bounce(5, 0), 0);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::sensors::display::error::DisplayError;
+use crate::sensors::display::layout::{Layout, Line};
+use crate::sensors::display::Display;
+
+// keys on the 4x4 keypad that drive a menu
+pub const KEY_UP: char = '2';
+pub const KEY_DOWN: char = '8';
+pub const KEY_SELECT: char = '#';
+pub const KEY_BACK: char = '*';
+
+// what a key press did to the menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    Moved,
+    Selected(usize),
+    Back,
+}
+
+// a list of choices shown with the current one highlighted, e.g. the admin functions
+pub struct Menu {
+    items: Vec<String>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new<S: Into<String>>(items: impl IntoIterator<Item = S>) -> Self {
+        Menu {
+            items: items.into_iter().map(Into::into).collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.items
+            .iter()
+            .fold(Layout::new(), |layout, item| layout.line(Line::new(item.as_str())))
+            .select(self.selected)
+    }
+
+    // moves the selection without wrapping round, None for keys the menu ignores
+    pub fn handle_key(&mut self, key: char) -> Option<MenuAction> {
+        match key {
+            KEY_UP if self.selected > 0 => {
+                self.selected -= 1;
+                Some(MenuAction::Moved)
+            }
+            KEY_DOWN if self.selected + 1 < self.items.len() => {
+                self.selected += 1;
+                Some(MenuAction::Moved)
+            }
+            KEY_SELECT if !self.items.is_empty() => Some(MenuAction::Selected(self.selected)),
+            KEY_BACK => Some(MenuAction::Back),
+            _ => None,
+        }
+    }
+
+    // shows the menu until an item is chosen, None when backed out of. `read_key` is polled
+    // like the keypad's read_key, so either keypad (or a test) can drive it
+    pub fn run(
+        &mut self,
+        display: &mut dyn Display,
+        mut read_key: impl FnMut() -> Option<char>,
+    ) -> Result<Option<usize>, DisplayError> {
+        display.show(&self.layout())?;
+
+        loop {
+            let Some(key) = read_key() else { continue };
+            // any key wakes the screen, even one the menu ignores
+            display.wake()?;
+            match self.handle_key(key) {
+                Some(MenuAction::Moved) => display.show(&self.layout())?,
+                Some(MenuAction::Selected(index)) => return Ok(Some(index)),
+                Some(MenuAction::Back) => return Ok(None),
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::display::status::Status;
+
+    // counts what a menu asks of the display
+    #[derive(Default)]
+    struct Recorder {
+        shown: usize,
+        wakes: usize,
+    }
+
+    impl Display for Recorder {
+        fn render(&mut self, _layout: &Layout, _tick: u32) -> Result<bool, DisplayError> {
+            self.shown += 1;
+            Ok(false)
+        }
+
+        fn set_status(&mut self, _status: &Status) -> Result<(), DisplayError> {
+            Ok(())
+        }
+
+        fn wake(&mut self) -> Result<(), DisplayError> {
+            self.wakes += 1;
+            Ok(())
+        }
+
+        fn cleanup(&mut self) -> Result<(), DisplayError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn selection_stops_at_both_ends() {
+        let mut menu = Menu::new(["Enroll", "Backup", "Exit"]);
+        assert_eq!(menu.handle_key(KEY_UP), None);
+        assert_eq!(menu.selected(), 0);
+
+        assert_eq!(menu.handle_key(KEY_DOWN), Some(MenuAction::Moved));
+        assert_eq!(menu.handle_key(KEY_DOWN), Some(MenuAction::Moved));
+        assert_eq!(menu.selected(), 2);
+        // no wrapping round to the top
+        assert_eq!(menu.handle_key(KEY_DOWN), None);
+        assert_eq!(menu.selected(), 2);
+
+        assert_eq!(menu.handle_key(KEY_UP), Some(MenuAction::Moved));
+        assert_eq!(menu.selected(), 1);
+    }
+
+    #[test]
+    fn select_and_back() {
+        let mut menu = Menu::new(["Enroll", "Backup", "Exit"]);
+        menu.handle_key(KEY_DOWN);
+        assert_eq!(menu.handle_key(KEY_SELECT), Some(MenuAction::Selected(1)));
+        assert_eq!(menu.handle_key(KEY_BACK), Some(MenuAction::Back));
+        // neither moves the selection
+        assert_eq!(menu.selected(), 1);
+    }
+
+    #[test]
+    fn other_keys_are_ignored() {
+        let mut menu = Menu::new(["Enroll", "Backup"]);
+        for key in ['0', '5', 'A', 'D', '\0'] {
+            assert_eq!(menu.handle_key(key), None, "{:?}", key);
+        }
+        assert_eq!(menu.selected(), 0);
+    }
+
+    #[test]
+    fn empty_menu_can_only_be_backed_out_of() {
+        let mut menu = Menu::new(Vec::<String>::new());
+        assert_eq!(menu.handle_key(KEY_DOWN), None);
+        assert_eq!(menu.handle_key(KEY_UP), None);
+        assert_eq!(menu.handle_key(KEY_SELECT), None);
+        assert_eq!(menu.handle_key(KEY_BACK), Some(MenuAction::Back));
+    }
+
+    #[test]
+    fn run_redraws_on_moves_and_returns_the_choice() {
+        let mut display = Recorder::default();
+        let mut keys = [KEY_DOWN, KEY_DOWN, KEY_UP, KEY_SELECT].into_iter();
+        let chosen = Menu::new(["Enroll", "Backup", "Exit"]).run(&mut display, || keys.next()).unwrap();
+
+        assert_eq!(chosen, Some(1));
+        assert_eq!(display.shown, 4);
+
+        let mut keys = [KEY_DOWN, KEY_BACK].into_iter();
+        assert_eq!(Menu::new(["Enroll", "Exit"]).run(&mut display, || keys.next()).unwrap(), None);
+    }
+
+    #[test]
+    fn every_key_wakes_the_screen() {
+        let mut display = Recorder::default();
+        // up at the top and 5 are ignored, the select still counts
+        let mut keys = [KEY_UP, '5', KEY_SELECT].into_iter();
+        let chosen = Menu::new(["Continue", "Exit"]).run(&mut display, || keys.next()).unwrap();
+
+        assert_eq!(chosen, Some(0));
+        assert_eq!(display.wakes, 3);
+        // only the first screen, nothing moved
+        assert_eq!(display.shown, 1);
+    }
+}
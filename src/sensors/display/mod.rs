@@ -0,0 +1,48 @@
+use crate::sensors::display::error::DisplayError;
+use crate::sensors::display::layout::Layout;
+use crate::sensors::display::status::Status;
+
+pub mod error;
+pub mod framebuffer;
+pub mod indicator;
+pub mod layout;
+pub mod menu;
+pub mod qr;
+pub mod renderer;
+pub mod screensaver;
+pub mod status;
+pub mod terminal;
+
+// what the app draws screens on, a panel or a stand-in for one
+pub trait Display {
+    // draws one frame of a screen, replacing whatever was on screen. `tick` counts render
+    // ticks since the screen was first shown, true if later ticks would look different
+    fn render(&mut self, layout: &Layout, tick: u32) -> Result<bool, DisplayError>;
+
+    // shows a laid out screen as it first appears, see renderer for one that animates
+    fn show(&mut self, layout: &Layout) -> Result<(), DisplayError> {
+        self.render(layout, 0).map(|_| ())
+    }
+
+    // shows lines of plain text, left aligned and wrapped to fit
+    fn draw(&mut self, data: Vec<&str>) -> Result<(), DisplayError> {
+        self.show(&Layout::from(data))
+    }
+
+    // keeps a status bar on the top rows from now on, screens are drawn below it
+    fn set_status(&mut self, status: &Status) -> Result<(), DisplayError>;
+
+    // turns the panel's brightness down to save it while idle, no-op where it can't be
+    fn set_dimmed(&mut self, _dimmed: bool) -> Result<(), DisplayError> {
+        Ok(())
+    }
+
+    // someone is at the kiosk, e.g. a key press or a finger, so a screensaver should give
+    // way to the screen. no-op for displays without one
+    fn wake(&mut self) -> Result<(), DisplayError> {
+        Ok(())
+    }
+
+    // blanks the screen
+    fn cleanup(&mut self) -> Result<(), DisplayError>;
+}
@@ -0,0 +1,111 @@
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+};
+use qrcodegen::{QrCode, QrCodeEcc};
+
+// light modules around the code, the spec asks for 4 but a panel this small can't spare them
+// and phone scanners cope with one
+const QUIET_ZONE: i32 = 1;
+
+// low error correction keeps the code small enough for a few pixels per module,
+// nothing gets scratched on a screen. None when the data won't fit in a QR code at all
+pub fn encode(data: &str) -> Option<QrCode> {
+    QrCode::encode_text(data, QrCodeEcc::Low).ok()
+}
+
+// how wide the code is drawn at the largest whole number of pixels per module that fits
+pub fn side(code: &QrCode, height: u32) -> u32 {
+    let modules = (code.size() + 2 * QUIET_ZONE) as u32;
+    (height / modules).max(1) * modules
+}
+
+// draws the code at the left of the area, vertically centred, as dark modules on a lit
+// square because scanners expect a dark code on a light background. Returns its width
+pub fn draw_qr<D>(target: &mut D, code: &QrCode, area: Rectangle) -> Result<u32, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let side = side(code, area.size.height);
+    let scale = side / (code.size() + 2 * QUIET_ZONE) as u32;
+    let top_left = area.top_left + Point::new(0, (area.size.height as i32 - side as i32) / 2);
+
+    Rectangle::new(top_left, Size::new(side, side))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(target)?;
+
+    let dark = PrimitiveStyle::with_fill(BinaryColor::Off);
+    for y in 0..code.size() {
+        for x in (0..code.size()).filter(|&x| code.get_module(x, y)) {
+            let position = top_left + Point::new(x + QUIET_ZONE, y + QUIET_ZONE) * scale as i32;
+            Rectangle::new(position, Size::new(scale, scale)).into_styled(dark).draw(target)?;
+        }
+    }
+
+    Ok(side)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::display::framebuffer::FrameBuffer;
+    use crate::sensors::display::layout::{draw_layout, Layout};
+
+    #[test]
+    fn side_is_whole_modules_with_the_quiet_zone() {
+        let code = encode("ATT-0042").unwrap();
+        assert_eq!(code.size(), 21);
+        // 21 modules and a module of quiet zone either side, one or two pixels each
+        assert_eq!(side(&code, 32), 23);
+        assert_eq!(side(&code, 64), 46);
+        // never below a pixel per module, even when that overflows
+        assert_eq!(side(&code, 10), 23);
+    }
+
+    #[test]
+    fn code_is_drawn_inside_a_lit_quiet_zone() {
+        let code = encode("ATT-0042").unwrap();
+        let mut frame = FrameBuffer::with_size(128, 32);
+        let area = frame.bounding_box();
+        assert_eq!(draw_qr(&mut frame, &code, area).unwrap(), 23);
+
+        // centred vertically: rows 4 to 26, the outer ring lit
+        let (top, bottom) = (4, 26);
+        assert!((0..23).all(|x| frame.pixel(x, top) && frame.pixel(x, bottom)));
+        assert!((top..=bottom).all(|y| frame.pixel(0, y) && frame.pixel(22, y)));
+        assert!(!frame.pixel(0, top - 1) && !frame.pixel(0, bottom + 1) && !frame.pixel(23, top));
+
+        // every module in place, the finder pattern's corner dark
+        assert!(!frame.pixel(1, top + 1));
+        for y in 0..code.size() {
+            for x in 0..code.size() {
+                let pixel = frame.pixel((x + 1) as u32, top + 1 + y as u32);
+                assert_eq!(pixel, !code.get_module(x, y), "module {},{}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn codes_too_big_for_the_panel_scroll_as_text_instead() {
+        let long = "https://attendance.example.com/check-in/0042?site=front-entrance&token=5f3a9c";
+        let code = encode(long).unwrap();
+        assert!(side(&code, 32) > 32, "{} modules", code.size());
+
+        let mut frame = FrameBuffer::with_size(128, 32);
+        draw_layout(&mut frame, &Layout::new().qr(long), 0).unwrap();
+        // no lit square on the left, the data is written out after the margin
+        assert!((0..32).all(|y| !frame.pixel(0, y)));
+        assert!((0..32).any(|y| (0..128).any(|x| frame.pixel(x, y))));
+
+        // and at 64 pixels the same code fits
+        let mut frame = FrameBuffer::with_size(128, 64);
+        draw_layout(&mut frame, &Layout::new().qr(long), 0).unwrap();
+        assert!((0..64).any(|y| frame.pixel(0, y)));
+    }
+
+    #[test]
+    fn data_too_long_for_any_code_is_not_encoded() {
+        assert!(encode(&"x".repeat(3000)).is_none());
+    }
+}
@@ -0,0 +1,425 @@
+use crate::sensors::display::error::DisplayError;
+use crate::sensors::display::layout::Layout;
+use crate::sensors::display::screensaver::{SaverMode, Screensaver};
+use crate::sensors::display::status::{clock, Status};
+use crate::sensors::display::Display;
+
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// how often an animated screen is redrawn
+pub const TICK: Duration = Duration::from_millis(100);
+
+// how often a still screen wakes up to see if the status bar's clock has moved on or the
+// screensaver is due, and how often a frame that failed to draw is tried again
+const CLOCK_CHECK: Duration = Duration::from_secs(1);
+
+enum Message {
+    Show(Layout),
+    Status(Status),
+    Screensaver(Option<Screensaver>),
+    Wake,
+    Cleanup,
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Idle {
+    Awake,
+    Dimmed,
+    // the screensaver is drawn instead of the screen
+    Saving,
+}
+
+// owns a display on its own thread and keeps animating the current screen, marquees and
+// pages, between the app's calls so the main loop never waits on a redraw. Also dims and
+// then hides a screen left up too long once given a screensaver
+pub struct Renderer {
+    sender: Sender<Message>,
+    worker: JoinHandle<()>,
+}
+
+impl Renderer {
+    pub fn start(display: Box<dyn Display + Send>) -> Self {
+        Self::with_clock(display, Instant::now)
+    }
+
+    // times the screensaver with `now` instead of the system clock, so tests can move it on
+    pub fn with_clock(display: Box<dyn Display + Send>, now: impl Fn() -> Instant + Send + 'static) -> Self {
+        let (sender, messages) = mpsc::channel();
+        let worker = thread::spawn(move || {
+            let mut display = display;
+            let mut screen: Option<Layout> = None;
+            let mut tick = 0;
+            let mut animated = false;
+            // the time on the status bar, None until there is one
+            let mut shown_clock: Option<String> = None;
+            // the last frame didn't reach the display, e.g. a loose cable
+            let mut failed = false;
+            let mut screensaver: Option<Screensaver> = None;
+            let mut idle = Idle::Awake;
+            let mut last_activity = now();
+            // the saver's drift is timed from when it took over
+            let mut saving_since = now();
+
+            loop {
+                // sleeps until the next message when nothing on screen moves
+                let message = if animated {
+                    messages.recv_timeout(TICK)
+                } else if shown_clock.is_some() || failed || screensaver.is_some() {
+                    messages.recv_timeout(CLOCK_CHECK)
+                } else {
+                    messages.recv().map_err(|_| RecvTimeoutError::Disconnected)
+                };
+
+                // a new screen or a wake up, either means someone is at the kiosk
+                let mut active = false;
+                let mut redraw = match message {
+                    // the same screen again, e.g. from each pass of a polling loop, carries on
+                    // animating and doesn't count as anyone being there
+                    Ok(Message::Show(layout)) if screen.as_ref() == Some(&layout) => false,
+                    Ok(Message::Show(layout)) => {
+                        tick = 0;
+                        screen = Some(layout);
+                        active = true;
+                        true
+                    }
+                    Ok(Message::Status(status)) => {
+                        if let Err(e) = display.set_status(&status) {
+                            Self::log(&format!("Could not set the status bar: {}", e), true);
+                        }
+                        shown_clock = Some(clock());
+                        tick += 1;
+                        true
+                    }
+                    Ok(Message::Screensaver(config)) => {
+                        screensaver = config;
+                        active = true;
+                        false
+                    }
+                    Ok(Message::Wake) => {
+                        active = true;
+                        false
+                    }
+                    Ok(Message::Cleanup) => {
+                        if let Err(e) = display.cleanup() {
+                            Self::log(&format!("Could not clean up the screen: {}", e), true);
+                        }
+                        screen = None;
+                        animated = false;
+                        failed = false;
+                        false
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        let due = match idle {
+                            // the clock drifts on, a blank saver has nothing to redraw
+                            Idle::Saving => screensaver.is_some_and(|saver| saver.mode == SaverMode::Clock),
+                            _ => animated || failed || shown_clock.as_ref().is_some_and(|shown| *shown != clock()),
+                        };
+                        if due {
+                            tick += 1;
+                        }
+                        due
+                    }
+                    Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+                };
+
+                if active {
+                    last_activity = now();
+                    if idle != Idle::Awake {
+                        if let Err(e) = display.set_dimmed(false) {
+                            Self::log(&format!("Could not brighten the screen: {}", e), true);
+                        }
+                        redraw |= idle == Idle::Saving;
+                        idle = Idle::Awake;
+                    }
+                }
+
+                if let Some(saver) = screensaver {
+                    let quiet = now().saturating_duration_since(last_activity);
+                    if idle == Idle::Awake && quiet >= saver.dim_after {
+                        if let Err(e) = display.set_dimmed(true) {
+                            Self::log(&format!("Could not dim the screen: {}", e), true);
+                        }
+                        idle = Idle::Dimmed;
+                    }
+                    if idle == Idle::Dimmed && quiet >= saver.saver_after {
+                        idle = Idle::Saving;
+                        saving_since = now();
+                        redraw = true;
+                    }
+                }
+
+                let saver_screen = match (idle, screensaver) {
+                    (Idle::Saving, Some(saver)) => Some(saver.layout()),
+                    _ => None,
+                };
+                let (shown, shown_tick) = match &saver_screen {
+                    Some(layout) => {
+                        let drifted = now().saturating_duration_since(saving_since);
+                        (Some(layout), (drifted.as_millis() / TICK.as_millis()) as u32)
+                    }
+                    None => (screen.as_ref(), tick),
+                };
+
+                if let (true, Some(layout)) = (redraw, shown) {
+                    // only the first failure is logged, the display is retried until it recovers
+                    match display.render(layout, shown_tick) {
+                        Ok(moving) => {
+                            if failed {
+                                Self::log("Display is drawing again", false);
+                            }
+                            // the saver moves far slower than a tick, it is redrawn on clock checks
+                            animated = moving && saver_screen.is_none();
+                            failed = false;
+                        }
+                        Err(e) => {
+                            if !failed {
+                                Self::log(&format!("Could not draw the screen: {}", e), true);
+                            }
+                            animated = false;
+                            failed = true;
+                        }
+                    }
+                    if shown_clock.is_some() {
+                        shown_clock = Some(clock());
+                    }
+                }
+            }
+        });
+
+        Renderer { sender, worker }
+    }
+
+    // waits for screens already sent to be drawn
+    pub fn stop(self) {
+        let _ = self.sender.send(Message::Stop);
+        let _ = self.worker.join();
+    }
+
+    // wakes the screen from other threads, e.g. a sensor or keypad noticing someone while the
+    // main loop is blocked
+    pub fn waker(&self) -> Waker {
+        Waker { sender: self.sender.clone() }
+    }
+
+    // dims and then hides screens left up too long from now on, None keeps them on
+    pub fn set_screensaver(&mut self, screensaver: Option<Screensaver>) -> Result<(), DisplayError> {
+        self.send(Message::Screensaver(screensaver))
+    }
+
+    fn send(&self, message: Message) -> Result<(), DisplayError> {
+        self.sender.send(message).map_err(|_| DisplayError::Stopped)
+    }
+
+    fn log(message: &str, warning: bool) {
+        println!("{} Renderer: {}", if warning { "[WARNING]" } else { "[INFO]" }, message);
+    }
+}
+
+#[derive(Clone)]
+pub struct Waker {
+    sender: Sender<Message>,
+}
+
+impl Waker {
+    pub fn wake(&self) -> Result<(), DisplayError> {
+        self.sender.send(Message::Wake).map_err(|_| DisplayError::Stopped)
+    }
+}
+
+impl Display for Renderer {
+    // the render thread draws this and every later tick and logs what goes wrong there,
+    // only a stopped thread is reported back here
+    fn render(&mut self, layout: &Layout, _tick: u32) -> Result<bool, DisplayError> {
+        self.send(Message::Show(layout.clone()))?;
+        Ok(false)
+    }
+
+    fn set_status(&mut self, status: &Status) -> Result<(), DisplayError> {
+        self.send(Message::Status(*status))
+    }
+
+    fn wake(&mut self) -> Result<(), DisplayError> {
+        self.send(Message::Wake)
+    }
+
+    fn cleanup(&mut self) -> Result<(), DisplayError> {
+        self.send(Message::Cleanup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Seen {
+        Screen(Layout),
+        // the screensaver, told apart by its drift since the clock's text changes
+        Saver { lines: usize },
+        Dimmed(bool),
+    }
+
+    // passes on what the render thread does to the display, in order
+    struct Recorder(Sender<Seen>);
+
+    impl Display for Recorder {
+        fn render(&mut self, layout: &Layout, _tick: u32) -> Result<bool, DisplayError> {
+            let seen = match layout.is_drifting() {
+                true => Seen::Saver { lines: layout.lines().len() },
+                false => Seen::Screen(layout.clone()),
+            };
+            let _ = self.0.send(seen);
+            Ok(false)
+        }
+
+        fn set_status(&mut self, _status: &Status) -> Result<(), DisplayError> {
+            Ok(())
+        }
+
+        fn set_dimmed(&mut self, dimmed: bool) -> Result<(), DisplayError> {
+            let _ = self.0.send(Seen::Dimmed(dimmed));
+            Ok(())
+        }
+
+        fn cleanup(&mut self) -> Result<(), DisplayError> {
+            Ok(())
+        }
+    }
+
+    // a renderer on a clock that only moves when the test says so
+    struct Harness {
+        renderer: Renderer,
+        seen: Receiver<Seen>,
+        clock: Arc<Mutex<Instant>>,
+    }
+
+    impl Harness {
+        fn start(screensaver: Option<Screensaver>) -> Self {
+            let (sender, seen) = mpsc::channel();
+            let clock = Arc::new(Mutex::new(Instant::now()));
+            let now = Arc::clone(&clock);
+            let mut renderer = Renderer::with_clock(Box::new(Recorder(sender)), move || *now.lock().unwrap());
+            renderer.set_screensaver(screensaver).unwrap();
+            Harness { renderer, seen, clock }
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.clock.lock().unwrap() += by;
+        }
+
+        // makes the thread look at the time without counting as someone being there
+        fn check(&mut self) {
+            self.renderer.set_status(&Status::default()).unwrap();
+        }
+
+        // waits for the thread to get this far, so the clock only moves between its steps
+        fn expect(&self, expected: &[Seen]) {
+            for expected in expected {
+                assert_eq!(&self.seen.recv_timeout(Duration::from_secs(5)).unwrap(), expected);
+            }
+        }
+
+        fn finish(self) {
+            self.renderer.stop();
+            assert_eq!(self.seen.try_iter().collect::<Vec<_>>(), []);
+        }
+    }
+
+    fn saver(mode: SaverMode) -> Option<Screensaver> {
+        Some(Screensaver { dim_after: Duration::from_secs(30), saver_after: Duration::from_secs(120), mode })
+    }
+
+    fn screen() -> Layout {
+        Layout::new().centred("Place your finger")
+    }
+
+    #[test]
+    fn idle_screen_dims_then_gives_way_to_the_clock() {
+        let mut harness = Harness::start(saver(SaverMode::Clock));
+        harness.renderer.show(&screen()).unwrap();
+        harness.expect(&[Seen::Screen(screen())]);
+
+        harness.advance(Duration::from_secs(29));
+        harness.check();
+        harness.expect(&[Seen::Screen(screen())]);
+
+        harness.advance(Duration::from_secs(1));
+        harness.check();
+        harness.expect(&[Seen::Dimmed(true), Seen::Screen(screen())]);
+
+        harness.advance(Duration::from_secs(89));
+        harness.check();
+        harness.expect(&[Seen::Screen(screen())]);
+
+        harness.advance(Duration::from_secs(1));
+        harness.check();
+        harness.expect(&[Seen::Saver { lines: 1 }]);
+        harness.finish();
+    }
+
+    #[test]
+    fn blank_saver_draws_nothing() {
+        let mut harness = Harness::start(saver(SaverMode::Blank));
+        harness.renderer.show(&screen()).unwrap();
+        harness.expect(&[Seen::Screen(screen())]);
+
+        harness.advance(Duration::from_secs(120));
+        harness.check();
+        harness.expect(&[Seen::Dimmed(true), Seen::Saver { lines: 0 }]);
+        harness.finish();
+    }
+
+    #[test]
+    fn wake_brightens_and_brings_the_screen_back() {
+        let mut harness = Harness::start(saver(SaverMode::Clock));
+        harness.renderer.show(&screen()).unwrap();
+        harness.expect(&[Seen::Screen(screen())]);
+
+        harness.advance(Duration::from_secs(120));
+        harness.check();
+        harness.expect(&[Seen::Dimmed(true), Seen::Saver { lines: 1 }]);
+
+        harness.renderer.wake().unwrap();
+        harness.expect(&[Seen::Dimmed(false), Seen::Screen(screen())]);
+
+        // the timeouts start again from the wake
+        harness.advance(Duration::from_secs(29));
+        harness.check();
+        harness.expect(&[Seen::Screen(screen())]);
+        harness.finish();
+    }
+
+    #[test]
+    fn showing_the_same_screen_again_is_not_activity() {
+        let mut harness = Harness::start(saver(SaverMode::Blank));
+        harness.renderer.show(&screen()).unwrap();
+        harness.expect(&[Seen::Screen(screen())]);
+
+        harness.advance(Duration::from_secs(30));
+        harness.renderer.show(&screen()).unwrap();
+        harness.expect(&[Seen::Dimmed(true)]);
+
+        // a new screen is, and brightens the panel
+        let welcome = Layout::new().centred("Welcome");
+        harness.renderer.show(&welcome).unwrap();
+        harness.expect(&[Seen::Dimmed(false), Seen::Screen(welcome)]);
+        harness.finish();
+    }
+
+    #[test]
+    fn no_screensaver_stays_lit() {
+        let mut harness = Harness::start(None);
+        harness.renderer.show(&screen()).unwrap();
+        harness.expect(&[Seen::Screen(screen())]);
+
+        harness.advance(Duration::from_secs(3600));
+        harness.check();
+        harness.expect(&[Seen::Screen(screen())]);
+        harness.finish();
+    }
+}
@@ -0,0 +1,75 @@
+use crate::sensors::display::layout::Layout;
+use crate::sensors::display::status::clock;
+
+use std::time::Duration;
+
+// what takes the place of a screen nobody has touched in a while
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaverMode {
+    // nothing lit at all
+    Blank,
+    // the time, drifting round the panel so no pixel stays lit
+    Clock,
+}
+
+impl SaverMode {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "blank" | "off" => Some(SaverMode::Blank),
+            "clock" => Some(SaverMode::Clock),
+            _ => None,
+        }
+    }
+}
+
+// dims the panel once nothing has happened for `dim_after`, then swaps the screen for the
+// saver after `saver_after` so static text doesn't burn into the OLED
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Screensaver {
+    pub dim_after: Duration,
+    pub saver_after: Duration,
+    pub mode: SaverMode,
+}
+
+impl Default for Screensaver {
+    fn default() -> Self {
+        Screensaver {
+            dim_after: Duration::from_secs(30),
+            saver_after: Duration::from_secs(120),
+            mode: SaverMode::Clock,
+        }
+    }
+}
+
+impl Screensaver {
+    // drawn instead of the idle screen, without the status bar
+    pub fn layout(&self) -> Layout {
+        match self.mode {
+            SaverMode::Blank => Layout::new().drifting(),
+            SaverMode::Clock => Layout::new().headline(clock()).drifting(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_saver_modes() {
+        assert_eq!(SaverMode::parse("Blank"), Some(SaverMode::Blank));
+        assert_eq!(SaverMode::parse(" off"), Some(SaverMode::Blank));
+        assert_eq!(SaverMode::parse("clock"), Some(SaverMode::Clock));
+        assert_eq!(SaverMode::parse("stars"), None);
+    }
+
+    #[test]
+    fn blank_saver_is_empty_and_clock_saver_has_the_time() {
+        let blank = Screensaver { mode: SaverMode::Blank, ..Screensaver::default() }.layout();
+        assert!(blank.is_drifting() && blank.lines().is_empty());
+
+        let clock = Screensaver::default().layout();
+        assert!(clock.is_drifting());
+        assert_eq!(clock.lines().len(), 1);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::sensors::display::layout::{draw_layout, Layout};
+
+use embedded_graphics::{
+    image::{Image, ImageRaw},
+    mono_font::{ascii::FONT_5X8, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle, Rectangle},
+    text::{Baseline, Text},
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// rows taken by the bar, 8 for icons and text, its underline and a blank row below it
+pub const BAR_HEIGHT: u32 = 10;
+
+const ICON_SIZE: u32 = 8;
+const ICON_GAP: i32 = 3;
+
+// 8x8 icons, one byte per row with the leftmost pixel in the top bit
+const SENSOR_OK: [u8; 8] = [0x3C, 0x42, 0x99, 0xA5, 0xA5, 0xA5, 0x24, 0x00];
+const SENSOR_LOST: [u8; 8] = [0x3C, 0x42, 0xA5, 0x99, 0x99, 0xA5, 0x42, 0x3C];
+const NETWORK_ONLINE: [u8; 8] = [0x03, 0x03, 0x0F, 0x0F, 0x3F, 0x3F, 0xFF, 0xFF];
+const NETWORK_OFFLINE: [u8; 8] = [0x88, 0x50, 0x20, 0x50, 0x88, 0x00, 0xFF, 0xFF];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SensorState {
+    #[default]
+    Connected,
+    Lost,
+}
+
+// whether the kiosk could reach anything, see network::network_state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NetworkState {
+    #[default]
+    Offline,
+    Online,
+}
+
+// what the status bar shows besides the time. There is no upload of check-ins yet, so no
+// sync state or pending count to show either
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Status {
+    pub sensor: SensorState,
+    pub network: NetworkState,
+}
+
+// the layout below a status bar when there is one, otherwise on the whole target. QR codes
+// get the whole target too, every row makes their modules bigger, and so do drifting screens
+// since a bar that never moves would burn in
+pub fn draw_screen<D>(target: &mut D, layout: &Layout, status: Option<&Status>, tick: u32) -> Result<bool, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let Some(status) = status.filter(|_| layout.qr_data().is_none() && !layout.is_drifting()) else {
+        return draw_layout(target, layout, tick);
+    };
+
+    let area = target.bounding_box();
+    draw_status_bar(target, status)?;
+
+    let below = Rectangle::new(
+        area.top_left + Point::new(0, BAR_HEIGHT as i32),
+        Size::new(area.size.width, area.size.height.saturating_sub(BAR_HEIGHT)),
+    );
+    draw_layout(&mut target.cropped(&below), layout, tick)
+}
+
+// the clock on the left, network and sensor icons on the right
+pub fn draw_status_bar<D>(target: &mut D, status: &Status) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let area = target.bounding_box();
+    let top_left = area.top_left;
+    let right = top_left.x + area.size.width as i32 - 1;
+
+    Rectangle::new(top_left, Size::new(area.size.width, BAR_HEIGHT))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+        .draw(target)?;
+
+    let text_style = MonoTextStyle::new(&FONT_5X8, BinaryColor::On);
+    Text::with_baseline(&clock(), top_left + Point::new(1, 0), text_style, Baseline::Top).draw(target)?;
+
+    let sensor = match status.sensor {
+        SensorState::Connected => &SENSOR_OK,
+        SensorState::Lost => &SENSOR_LOST,
+    };
+    let network = match status.network {
+        NetworkState::Offline => &NETWORK_OFFLINE,
+        NetworkState::Online => &NETWORK_ONLINE,
+    };
+    let mut x = right + 1;
+    for icon in [sensor, network] {
+        x -= ICON_SIZE as i32;
+        draw_icon(target, icon, Point::new(x, top_left.y))?;
+        x -= ICON_GAP;
+    }
+
+    Line::new(
+        top_left + Point::new(0, ICON_SIZE as i32),
+        Point::new(right, top_left.y + ICON_SIZE as i32),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+    .draw(target)
+}
+
+fn draw_icon<D>(target: &mut D, icon: &[u8; 8], position: Point) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let raw = ImageRaw::<BinaryColor>::new(icon, ICON_SIZE);
+    Image::new(&raw, position).draw(target)
+}
+
+// HH:MM in local time, UTC where there is no C library to ask
+pub fn clock() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let (hours, minutes) = local_time(now);
+    format!("{:02}:{:02}", hours, minutes)
+}
+
+#[cfg(unix)]
+fn local_time(unix_time: u64) -> (u32, u32) {
+    let time = unix_time as libc::time_t;
+    // SAFETY: localtime_r only writes into the tm we own, a zeroed tm is a valid value
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return utc_time(unix_time);
+    }
+    (tm.tm_hour as u32, tm.tm_min as u32)
+}
+
+#[cfg(not(unix))]
+fn local_time(unix_time: u64) -> (u32, u32) {
+    utc_time(unix_time)
+}
+
+fn utc_time(unix_time: u64) -> (u32, u32) {
+    let minutes = (unix_time / 60 % (24 * 60)) as u32;
+    (minutes / 60, minutes % 60)
+}
@@ -0,0 +1,214 @@
+use crate::sensors::display::error::DisplayError;
+use crate::sensors::display::framebuffer::FrameBuffer;
+use crate::sensors::display::layout::Layout;
+use crate::sensors::display::status::{draw_screen, Status};
+use crate::sensors::display::Display;
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+use std::convert::Infallible;
+use std::io::{self, Write};
+
+// how panel pixels map onto terminal cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminalStyle {
+    // one cell per 1x2 pixels, ▀ ▄ █, roughly square pixels in most fonts
+    HalfBlocks,
+    // one cell per 2x4 pixels, smaller but fits narrow terminals
+    Braille,
+}
+
+impl TerminalStyle {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "blocks" | "halfblocks" | "half-blocks" => Some(TerminalStyle::HalfBlocks),
+            "braille" => Some(TerminalStyle::Braille),
+            _ => None,
+        }
+    }
+}
+
+// draws into a framebuffer and prints it as a framed block of text on every flush,
+// so the whole kiosk flow can be watched on a laptop exactly as the OLED would show it
+pub struct TerminalDisplay {
+    frame: FrameBuffer,
+    style: TerminalStyle,
+    status: Option<Status>,
+}
+
+impl TerminalDisplay {
+    pub fn new(width: u32, height: u32, style: TerminalStyle) -> Self {
+        let display = TerminalDisplay {
+            frame: FrameBuffer::with_size(width, height),
+            style,
+            status: None,
+        };
+        display.log(&format!("Terminal display ready ({}x{}, {:?})", width, height, style), false);
+        display
+    }
+
+    // prints the current frame below whatever was logged since the last one
+    pub fn flush(&mut self) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        out.write_all(render(&self.frame, self.style).as_bytes())?;
+        out.flush()
+    }
+
+    // moves the cursor back up over the last printed frame and prints this one in its place
+    pub fn repaint(&mut self) -> io::Result<()> {
+        let text = render(&self.frame, self.style);
+        let mut out = io::stdout().lock();
+        write!(out, "\x1b[{}A{}", text.lines().count(), text)?;
+        out.flush()
+    }
+
+    fn log(&self, message: &str, warning: bool) {
+        println!("{} Display: {}", if warning { "[WARNING]" } else { "[INFO]" }, message);
+    }
+}
+
+// the frame as lines of text inside a box the size of the panel
+pub fn render(frame: &FrameBuffer, style: TerminalStyle) -> String {
+    let (cell_width, cell_height) = match style {
+        TerminalStyle::HalfBlocks => (1, 2),
+        TerminalStyle::Braille => (2, 4),
+    };
+    let columns = frame.width().div_ceil(cell_width) as usize;
+    let rows = frame.height().div_ceil(cell_height);
+
+    let mut text = format!("┌{}┐\n", "─".repeat(columns));
+    for row in 0..rows {
+        text.push('│');
+        for column in 0..columns as u32 {
+            let (x, y) = (column * cell_width, row * cell_height);
+            text.push(match style {
+                TerminalStyle::HalfBlocks => half_block(frame.pixel(x, y), frame.pixel(x, y + 1)),
+                TerminalStyle::Braille => braille(frame, x, y),
+            });
+        }
+        text.push_str("│\n");
+    }
+    text.push_str(&format!("└{}┘\n", "─".repeat(columns)));
+    text
+}
+
+fn half_block(top: bool, bottom: bool) -> char {
+    match (top, bottom) {
+        (false, false) => ' ',
+        (true, false) => '▀',
+        (false, true) => '▄',
+        (true, true) => '█',
+    }
+}
+
+// braille dots are numbered down the left column then the right, with the bottom row last
+fn braille(frame: &FrameBuffer, x: u32, y: u32) -> char {
+    const DOTS: [(u32, u32, u32); 8] = [
+        (0, 0, 0x01),
+        (0, 1, 0x02),
+        (0, 2, 0x04),
+        (1, 0, 0x08),
+        (1, 1, 0x10),
+        (1, 2, 0x20),
+        (0, 3, 0x40),
+        (1, 3, 0x80),
+    ];
+
+    let bits = DOTS
+        .iter()
+        .filter(|(dx, dy, _)| frame.pixel(x + dx, y + dy))
+        .fold(0, |bits, (_, _, bit)| bits | bit);
+    char::from_u32(0x2800 + bits).unwrap_or(' ')
+}
+
+impl OriginDimensions for TerminalDisplay {
+    fn size(&self) -> Size {
+        self.frame.size()
+    }
+}
+
+impl DrawTarget for TerminalDisplay {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.frame.draw_iter(pixels)
+    }
+}
+
+impl Display for TerminalDisplay {
+    // animation frames are printed over the frame before them instead of below it
+    fn render(&mut self, layout: &Layout, tick: u32) -> Result<bool, DisplayError> {
+        let Ok(animated) = draw_screen(&mut self.frame, layout, self.status.as_ref(), tick);
+        match tick {
+            0 => self.flush()?,
+            _ => self.repaint()?,
+        }
+        Ok(animated)
+    }
+
+    fn set_status(&mut self, status: &Status) -> Result<(), DisplayError> {
+        self.status = Some(*status);
+        Ok(())
+    }
+
+    fn cleanup(&mut self) -> Result<(), DisplayError> {
+        let Ok(()) = self.frame.clear(BinaryColor::Off);
+        self.flush()?;
+        self.log("Cleaned up the screen", false);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(width: u32, height: u32, lit: &[(i32, i32)]) -> FrameBuffer {
+        let mut frame = FrameBuffer::with_size(width, height);
+        let pixels = lit.iter().map(|&(x, y)| Pixel(Point::new(x, y), BinaryColor::On));
+        let Ok(()) = frame.draw_iter(pixels);
+        frame
+    }
+
+    #[test]
+    fn braille_cells_map_each_dot() {
+        // top left cell: first and last dot, top right: every dot, bottom right: the left column
+        let mut lit = vec![(0, 0), (1, 3)];
+        lit.extend((0..4).flat_map(|y| [(2, y), (3, y)]));
+        lit.extend((4..8).map(|y| (2, y)));
+
+        let text = render(&frame(4, 8, &lit), TerminalStyle::Braille);
+        assert_eq!(text, "┌──┐\n│⢁⣿│\n│⠀⡇│\n└──┘\n");
+    }
+
+    #[test]
+    fn braille_pads_partial_cells_with_blank_dots() {
+        let text = render(&frame(3, 5, &[(2, 4)]), TerminalStyle::Braille);
+        assert_eq!(text, "┌──┐\n│⠀⠀│\n│⠀⠁│\n└──┘\n");
+    }
+
+    #[test]
+    fn half_blocks_map_pixel_pairs() {
+        let text = render(&frame(2, 4, &[(0, 0), (0, 3), (1, 0), (1, 1)]), TerminalStyle::HalfBlocks);
+        assert_eq!(text, "┌──┐\n│▀█│\n│▄ │\n└──┘\n");
+    }
+
+    #[test]
+    fn panel_sized_frame_is_boxed_at_cell_resolution() {
+        let text = render(&FrameBuffer::with_size(128, 32), TerminalStyle::Braille);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 8 + 2);
+        assert!(lines.iter().all(|line| line.chars().count() == 64 + 2));
+        assert!(lines[1..9].iter().all(|line| line.chars().skip(1).take(64).all(|c| c == '\u{2800}')));
+    }
+
+    #[test]
+    fn parses_style_names() {
+        assert_eq!(TerminalStyle::parse(" Braille "), Some(TerminalStyle::Braille));
+        assert_eq!(TerminalStyle::parse("half-blocks"), Some(TerminalStyle::HalfBlocks));
+        assert_eq!(TerminalStyle::parse("ascii"), None);
+    }
+}
@@ -27,10 +27,7 @@ impl Keypad {
             out
         });
 
-        let cols: [InputPin; 4] = COL_PINS.map(|pin| {
-            let input = gpio.get(pin).unwrap().into_input_pulldown();
-            input
-        });
+        let cols: [InputPin; 4] = COL_PINS.map(|pin| gpio.get(pin).unwrap().into_input_pulldown());
 
         let keypad = Keypad { rows, cols };
         keypad.log("Keypad Initialised", false);
@@ -60,4 +57,10 @@ impl Keypad {
     fn log(&self, message: &str, warning: bool) {
         println!("{} Keypad: {}", if warning { "[WARNING]" } else { "[INFO]" }, message);
     }
-}
\ No newline at end of file
+}
+
+impl Default for Keypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
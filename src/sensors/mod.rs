@@ -1,3 +1,8 @@
+pub mod display;
 pub mod r305_fingerprint_sensor;
+#[cfg(feature = "rpi-gpio")]
 pub mod keypad;
-pub mod ssd1305_display;
\ No newline at end of file
+#[cfg(feature = "i2c-display")]
+pub mod ssd1305_display;
+#[cfg(feature = "simulator")]
+pub mod simulator;
@@ -0,0 +1,234 @@
+use crate::sensors::r305_fingerprint_sensor::error::FingerprintError;
+use crate::sensors::r305_fingerprint_sensor::lib::FingerprintSensor;
+use crate::sensors::r305_fingerprint_sensor::protocol::CharBuffer;
+
+use std::fs;
+use std::path::Path;
+
+// archive layout (all big endian):
+//   "ATPB" | version u16 | entry count u16
+//   per entry: slot u16 | length u16 | template bytes
+//   CRC-32 of everything above
+const ARCHIVE_MAGIC: &[u8; 4] = b"ATPB";
+const ARCHIVE_VERSION: u16 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedTemplate {
+    pub id: u16,
+    pub data: Vec<u8>,
+}
+
+// every template of a sensor's library, as written by backup
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateArchive {
+    pub templates: Vec<ArchivedTemplate>,
+}
+
+impl TemplateArchive {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(ARCHIVE_MAGIC);
+        bytes.extend_from_slice(&ARCHIVE_VERSION.to_be_bytes());
+        bytes.extend_from_slice(&(self.templates.len() as u16).to_be_bytes());
+
+        for template in &self.templates {
+            bytes.extend_from_slice(&template.id.to_be_bytes());
+            bytes.extend_from_slice(&(template.data.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(&template.data);
+        }
+
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_be_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, FingerprintError> {
+        if bytes.len() < 12 || &bytes[0..4] != ARCHIVE_MAGIC {
+            return Err(archive_error("Not a template archive"));
+        }
+
+        let (body, checksum) = bytes.split_at(bytes.len() - 4);
+        let checksum = u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+        if crc32(body) != checksum {
+            return Err(archive_error("Checksum mismatch, archive is corrupted"));
+        }
+
+        let version = u16::from_be_bytes([body[4], body[5]]);
+        if version != ARCHIVE_VERSION {
+            return Err(archive_error(&format!("Unsupported archive version {}", version)));
+        }
+
+        let count = u16::from_be_bytes([body[6], body[7]]) as usize;
+        let mut templates = Vec::with_capacity(count);
+        let mut offset = 8;
+
+        for _ in 0..count {
+            let header = body
+                .get(offset..offset + 4)
+                .ok_or_else(|| archive_error("Archive truncated"))?;
+            let id = u16::from_be_bytes([header[0], header[1]]);
+            let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+            offset += 4;
+
+            let data = body
+                .get(offset..offset + length)
+                .ok_or_else(|| archive_error("Archive truncated"))?;
+            templates.push(ArchivedTemplate { id, data: data.to_vec() });
+            offset += length;
+        }
+
+        if offset != body.len() {
+            return Err(archive_error("Trailing data after last template"));
+        }
+
+        Ok(TemplateArchive { templates })
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, FingerprintError> {
+        let bytes = fs::read(path).map_err(|e| archive_error(&e.to_string()))?;
+        Self::decode(&bytes)
+    }
+
+    // writes next to the target and renames, so a failed backup never clobbers a good one
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), FingerprintError> {
+        let path = path.as_ref();
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, self.encode()).map_err(|e| archive_error(&e.to_string()))?;
+        fs::rename(&temp, path).map_err(|e| archive_error(&e.to_string()))
+    }
+}
+
+impl FingerprintSensor {
+    // uploads every occupied slot into an archive file, returns how many were saved
+    pub fn backup(&mut self, path: impl AsRef<Path>) -> Result<usize, FingerprintError> {
+        let mut archive = TemplateArchive::default();
+
+        for id in self.occupied_slots()? {
+            let data = self.upload_template(id)?;
+            archive.templates.push(ArchivedTemplate { id, data });
+        }
+
+        archive.write(path)?;
+        self.log(&format!("Backed up {} templates", archive.templates.len()), false);
+        Ok(archive.templates.len())
+    }
+
+    // writes an archive back into the library and checks each slot matches what was saved
+    pub fn restore(&mut self, path: impl AsRef<Path>) -> Result<usize, FingerprintError> {
+        let archive = TemplateArchive::read(path)?;
+
+        for template in &archive.templates {
+            self.download_template(template.id, &template.data)?;
+
+            // buffer one still holds the archived template, compare the stored copy against it
+            self.load_char(CharBuffer::Two, template.id)?;
+            if self.match_chars()?.is_none() {
+                return Err(archive_error(&format!("Slot {} did not verify after restore", template.id)));
+            }
+        }
+
+        self.log(&format!("Restored {} templates", archive.templates.len()), false);
+        Ok(archive.templates.len())
+    }
+}
+
+fn archive_error(message: &str) -> FingerprintError {
+    FingerprintError::Archive(message.to_string())
+}
+
+// CRC-32 (IEEE), bitwise since archives are small
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::r305_fingerprint_sensor::emulator::{EmulatedModel, EmulatedSensor};
+
+    fn archive() -> TemplateArchive {
+        TemplateArchive {
+            templates: vec![
+                ArchivedTemplate { id: 0, data: vec![0xAB; 512] },
+                ArchivedTemplate { id: 7, data: (0..=255).collect() },
+                ArchivedTemplate { id: 999, data: Vec::new() },
+            ],
+        }
+    }
+
+    fn emulated(enrolled: u16) -> FingerprintSensor {
+        let mut emulator = EmulatedSensor::new(EmulatedModel::R305, 0xFFFFFFFF, 0, 1000);
+        emulator.fill_library(enrolled);
+        FingerprintSensor::with_transport(Box::new(emulator), 0xFFFFFFFF, 0).unwrap()
+    }
+
+    fn is_archive_error(result: Result<TemplateArchive, FingerprintError>, expected: &str) -> bool {
+        matches!(result, Err(FingerprintError::Archive(message)) if message.contains(expected))
+    }
+
+    #[test]
+    fn crc_matches_the_ieee_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn archive_round_trips() {
+        assert_eq!(TemplateArchive::decode(&archive().encode()).unwrap(), archive());
+        assert_eq!(TemplateArchive::decode(&TemplateArchive::default().encode()).unwrap(), TemplateArchive::default());
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = archive().encode();
+        bytes[0..4].copy_from_slice(b"PNG\0");
+        assert!(is_archive_error(TemplateArchive::decode(&bytes), "Not a template archive"));
+        assert!(is_archive_error(TemplateArchive::decode(b"ATPB"), "Not a template archive"));
+    }
+
+    #[test]
+    fn rejects_corrupted_archives() {
+        // one flipped bit in a template, then in the checksum itself
+        let mut bytes = archive().encode();
+        bytes[20] ^= 0x01;
+        assert!(is_archive_error(TemplateArchive::decode(&bytes), "Checksum mismatch"));
+
+        let mut bytes = archive().encode();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x80;
+        assert!(is_archive_error(TemplateArchive::decode(&bytes), "Checksum mismatch"));
+    }
+
+    #[test]
+    fn rejects_truncated_archives_with_a_valid_checksum() {
+        // claims one more template than it holds
+        let mut body = archive().encode();
+        body.truncate(body.len() - 4);
+        body[7] += 1;
+        let checksum = crc32(&body);
+        body.extend_from_slice(&checksum.to_be_bytes());
+        assert!(is_archive_error(TemplateArchive::decode(&body), "Archive truncated"));
+    }
+
+    #[test]
+    fn backup_restores_onto_an_empty_sensor() {
+        let path = std::env::temp_dir().join(format!("attendpi-backup-{}.atpb", std::process::id()));
+
+        let mut original = emulated(5);
+        assert_eq!(original.backup(&path).unwrap(), 5);
+
+        let mut blank = emulated(0);
+        let restored = blank.restore(&path);
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(restored.unwrap(), 5);
+        assert_eq!(blank.occupied_slots().unwrap(), original.occupied_slots().unwrap());
+    }
+}
@@ -0,0 +1,396 @@
+use crate::sensors::r305_fingerprint_sensor::protocol::*;
+use crate::sensors::r305_fingerprint_sensor::trace::PacketFramer;
+
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::Duration;
+
+const TEMPLATE_SIZE: usize = 512;
+const DATA_PACKET_SIZE: usize = 128;
+const MATCH_SCORE: u16 = 200;
+
+// confirmation codes the emulator answers with besides the ones in protocol.rs
+const ERROR_NO_IMAGE: u8 = 0x15;
+const ERROR_MERGE_FAILED: u8 = 0x0A;
+const ERROR_BAD_LOCATION: u8 = 0x0B;
+const ERROR_EMPTY_SLOT: u8 = 0x0C;
+
+// which module to pretend to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmulatedModel {
+    // ZFM command set only, no ReadProdInfo, aura LED or auto commands
+    R305,
+    R503,
+}
+
+// per template cost of a search, loosely after the datasheets' 1:1000 figures, so the
+// simulator feels like a module. made up, which is why bench-search refuses the emulator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchTiming {
+    pub normal: Duration,
+    pub high_speed: Duration,
+}
+
+impl Default for SearchTiming {
+    fn default() -> Self {
+        SearchTiming { normal: Duration::from_micros(800), high_speed: Duration::from_micros(150) }
+    }
+}
+
+// lets the host side put a "finger" on the emulated sensor while the driver owns it
+#[derive(Clone, Default)]
+pub struct EmulatorHandle {
+    finger: Arc<Mutex<Option<u32>>>,
+}
+
+impl EmulatorHandle {
+    // every finger id produces its own stable template
+    pub fn place_finger(&self, finger: u32) {
+        *self.finger.lock().unwrap() = Some(finger);
+    }
+
+    pub fn lift_finger(&self) {
+        *self.finger.lock().unwrap() = None;
+    }
+
+    fn finger(&self) -> Option<u32> {
+        *self.finger.lock().unwrap()
+    }
+}
+
+// an in-memory fingerprint module speaking the packet protocol, usable as a Transport
+pub struct EmulatedSensor {
+    model: EmulatedModel,
+    address: u32,
+    password: u32,
+    timing: SearchTiming,
+    handle: EmulatorHandle,
+    library: Vec<Option<Vec<u8>>>,
+    buffers: [Vec<u8>; 2],
+    image: Option<u32>,
+    // buffer a DownChar is filling, until its end packet arrives
+    downloading: Option<(usize, Vec<u8>)>,
+    framer: PacketFramer,
+    output: VecDeque<u8>,
+    // time the module is still "working" before its reply can be read
+    busy: Duration,
+}
+
+impl EmulatedSensor {
+    pub fn new(model: EmulatedModel, address: u32, password: u32, capacity: u16) -> Self {
+        EmulatedSensor {
+            model,
+            address,
+            password,
+            timing: SearchTiming::default(),
+            handle: EmulatorHandle::default(),
+            library: vec![None; capacity as usize],
+            buffers: [Vec::new(), Vec::new()],
+            image: None,
+            downloading: None,
+            framer: PacketFramer::default(),
+            output: VecDeque::new(),
+            busy: Duration::ZERO,
+        }
+    }
+
+    pub fn with_timing(mut self, timing: SearchTiming) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    pub fn handle(&self) -> EmulatorHandle {
+        self.handle.clone()
+    }
+
+    // fills slots with templates for fingers 0..count, e.g. to benchmark a full library
+    pub fn fill_library(&mut self, count: u16) {
+        for slot in 0..count.min(self.library.len() as u16) {
+            self.library[slot as usize] = Some(features(slot as u32));
+        }
+    }
+
+    fn newer(&self) -> bool {
+        self.model == EmulatedModel::R503
+    }
+
+    fn reply(&mut self, packet_type: u8, payload: &[u8]) {
+        self.output.extend(encode_packet(self.address, packet_type, payload));
+    }
+
+    fn ack(&mut self, code: u8, data: &[u8]) {
+        let mut payload = vec![code];
+        payload.extend_from_slice(data);
+        self.reply(ACK_PACKET, &payload);
+    }
+
+    fn handle_packet(&mut self, packet: &[u8]) {
+        if packet.len() < 12 {
+            return;
+        }
+        let packet_type = packet[6];
+        let payload = &packet[9..packet.len() - 2];
+
+        match packet_type {
+            COMMAND_PACKET => self.handle_command(payload),
+            DATA_PACKET | END_DATA_PACKET => {
+                if let Some((buffer, mut data)) = self.downloading.take() {
+                    data.extend_from_slice(payload);
+                    if packet_type == END_DATA_PACKET {
+                        self.buffers[buffer] = data;
+                    } else {
+                        self.downloading = Some((buffer, data));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_command(&mut self, payload: &[u8]) {
+        let Some(&code) = payload.first() else {
+            return;
+        };
+        let params = &payload[1..];
+        let byte = |i: usize| params.get(i).copied().unwrap_or(0);
+        let word = |i: usize| u16::from_be_bytes([byte(i), byte(i + 1)]);
+        let buffer = |i: usize| if byte(i) == 2 { 1 } else { 0 };
+
+        match code {
+            // VfyPwd
+            0x13 => {
+                let password = u32::from_be_bytes([byte(0), byte(1), byte(2), byte(3)]);
+                let code = if password == self.password { FINGERPRINT_OK } else { FINGERPRINT_ERROR_WRONG_PASSWORD };
+                self.ack(code, &[]);
+            }
+            // ReadSysPara
+            0x0F => {
+                let mut data = Vec::new();
+                data.extend_from_slice(&0u16.to_be_bytes());
+                data.extend_from_slice(&0x0009u16.to_be_bytes());
+                data.extend_from_slice(&(self.library.len() as u16).to_be_bytes());
+                data.extend_from_slice(&3u16.to_be_bytes());
+                data.extend_from_slice(&self.address.to_be_bytes());
+                data.extend_from_slice(&2u16.to_be_bytes());
+                data.extend_from_slice(&6u16.to_be_bytes());
+                self.ack(FINGERPRINT_OK, &data);
+            }
+            // ReadProdInfo
+            0x3C if self.newer() => {
+                let mut data = vec![0u8; 46];
+                data[..4].copy_from_slice(b"R503");
+                data[16..20].copy_from_slice(b"EMU0");
+                data[42..44].copy_from_slice(&(TEMPLATE_SIZE as u16).to_be_bytes());
+                data[44..46].copy_from_slice(&(self.library.len() as u16).to_be_bytes());
+                self.ack(FINGERPRINT_OK, &data);
+            }
+            // GenImg
+            0x01 => {
+                self.image = self.handle.finger();
+                let code = if self.image.is_some() { FINGERPRINT_OK } else { FINGERPRINT_ERROR_NO_FINGER };
+                self.ack(code, &[]);
+            }
+            // Img2Tz
+            0x02 => match self.image {
+                Some(finger) => {
+                    self.buffers[buffer(0)] = features(finger);
+                    self.ack(FINGERPRINT_OK, &[]);
+                }
+                None => self.ack(ERROR_NO_IMAGE, &[]),
+            },
+            // Match
+            0x03 => {
+                if !self.buffers[0].is_empty() && self.buffers[0] == self.buffers[1] {
+                    self.ack(FINGERPRINT_OK, &MATCH_SCORE.to_be_bytes());
+                } else {
+                    self.ack(FINGERPRINT_ERROR_NO_MATCH, &[0, 0]);
+                }
+            }
+            // Search, HighSpeedSearch
+            0x04 | 0x1B => {
+                let per_template = if code == 0x1B { self.timing.high_speed } else { self.timing.normal };
+                let result = self.search(buffer(0), word(1), word(3), per_template);
+                self.search_reply(result);
+            }
+            // RegModel
+            0x05 => {
+                if !self.buffers[0].is_empty() && self.buffers[0] == self.buffers[1] {
+                    self.ack(FINGERPRINT_OK, &[]);
+                } else {
+                    self.ack(ERROR_MERGE_FAILED, &[]);
+                }
+            }
+            // Store
+            0x06 => match self.library.get_mut(word(1) as usize) {
+                Some(slot) => {
+                    *slot = Some(self.buffers[buffer(0)].clone());
+                    self.ack(FINGERPRINT_OK, &[]);
+                }
+                None => self.ack(ERROR_BAD_LOCATION, &[]),
+            },
+            // LoadChar
+            0x07 => match self.library.get(word(1) as usize).cloned().flatten() {
+                Some(template) => {
+                    self.buffers[buffer(0)] = template;
+                    self.ack(FINGERPRINT_OK, &[]);
+                }
+                None => self.ack(ERROR_EMPTY_SLOT, &[]),
+            },
+            // UpChar
+            0x08 => {
+                self.ack(FINGERPRINT_OK, &[]);
+                let data = self.buffers[buffer(0)].clone();
+                let chunks: Vec<&[u8]> = data.chunks(DATA_PACKET_SIZE).collect();
+                for (i, chunk) in chunks.iter().enumerate() {
+                    let packet_type = if i + 1 == chunks.len() { END_DATA_PACKET } else { DATA_PACKET };
+                    self.reply(packet_type, chunk);
+                }
+            }
+            // DownChar
+            0x09 => {
+                self.downloading = Some((buffer(0), Vec::new()));
+                self.ack(FINGERPRINT_OK, &[]);
+            }
+            // Empty
+            0x0D => {
+                self.library.iter_mut().for_each(|slot| *slot = None);
+                self.ack(FINGERPRINT_OK, &[]);
+            }
+            // TempleteNum
+            0x1D => {
+                let count = self.library.iter().filter(|slot| slot.is_some()).count() as u16;
+                self.ack(FINGERPRINT_OK, &count.to_be_bytes());
+            }
+            // ReadIndexTable
+            0x1F => {
+                let mut table = [0u8; 32];
+                let first = byte(0) as usize * 256;
+                for (i, slot) in self.library.iter().skip(first).take(256).enumerate() {
+                    if slot.is_some() {
+                        table[i / 8] |= 1 << (i % 8);
+                    }
+                }
+                self.ack(FINGERPRINT_OK, &table);
+            }
+            // AuraLedConfig
+            0x35 if self.newer() => self.ack(FINGERPRINT_OK, &[]),
+            // AutoEnroll
+            0x31 if self.newer() => self.auto_enroll(word(0), byte(2).max(1)),
+            // AutoIdentify
+            0x32 if self.newer() => {
+                let Some(finger) = self.handle.finger() else {
+                    self.ack(FINGERPRINT_ERROR_NO_FINGER, &[0x01, 0, 0, 0, 0]);
+                    return;
+                };
+                self.ack(FINGERPRINT_OK, &[0x01, 0, 0, 0, 0]);
+                self.buffers[0] = features(finger);
+                let count = self.library.len() as u16;
+                match self.search(0, 0, count, self.timing.high_speed) {
+                    Some((slot, score)) => {
+                        let mut data = vec![0x05];
+                        data.extend_from_slice(&slot.to_be_bytes());
+                        data.extend_from_slice(&score.to_be_bytes());
+                        self.ack(FINGERPRINT_OK, &data);
+                    }
+                    None => self.ack(FINGERPRINT_ERROR_NO_TEMPLATE_FOUND, &[0x05, 0, 0, 0, 0]),
+                }
+            }
+            // anything else, as a module without the instruction would answer
+            _ => self.ack(FINGERPRINT_ERROR_COMMUNICATION, &[]),
+        }
+    }
+
+    fn search(&mut self, buffer: usize, start: u16, count: u16, per_template: Duration) -> Option<(u16, u16)> {
+        let wanted = &self.buffers[buffer];
+        let range = self.library.iter().enumerate().skip(start as usize).take(count as usize);
+        let occupied = range.clone().filter(|(_, slot)| slot.is_some()).count() as u32;
+        self.busy += per_template * occupied;
+
+        if wanted.is_empty() {
+            return None;
+        }
+        range
+            .filter_map(|(slot, template)| Some((slot, template.as_ref()?)))
+            .find(|(_, template)| *template == wanted)
+            .map(|(slot, _)| (slot as u16, MATCH_SCORE))
+    }
+
+    fn search_reply(&mut self, result: Option<(u16, u16)>) {
+        match result {
+            Some((slot, score)) => {
+                let mut data = slot.to_be_bytes().to_vec();
+                data.extend_from_slice(&score.to_be_bytes());
+                self.ack(FINGERPRINT_OK, &data);
+            }
+            None => self.ack(FINGERPRINT_ERROR_NO_TEMPLATE_FOUND, &[0, 0, 0, 0]),
+        }
+    }
+
+    fn auto_enroll(&mut self, id: u16, captures: u8) {
+        let Some(finger) = self.handle.finger() else {
+            self.ack(FINGERPRINT_ERROR_NO_FINGER, &[0x01, 0x01]);
+            return;
+        };
+        if id as usize >= self.library.len() {
+            self.ack(ERROR_BAD_LOCATION, &[0x00, 0x00]);
+            return;
+        }
+
+        self.ack(FINGERPRINT_OK, &[0x00, 0x00]);
+        for capture in 1..=captures {
+            self.ack(FINGERPRINT_OK, &[0x01, capture]);
+            self.ack(FINGERPRINT_OK, &[0x02, capture]);
+        }
+        self.ack(FINGERPRINT_OK, &[0x04, 0xF0]);
+        self.library[id as usize] = Some(features(finger));
+        self.ack(FINGERPRINT_OK, &[0x06, 0xF2]);
+    }
+}
+
+impl Read for EmulatedSensor {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // a real port waits out its timeout when the module stays quiet
+        if self.output.is_empty() {
+            return Err(io::Error::new(ErrorKind::TimedOut, "Emulated sensor has nothing to send"));
+        }
+
+        if !self.busy.is_zero() {
+            sleep(self.busy);
+            self.busy = Duration::ZERO;
+        }
+
+        let n = buf.len().min(self.output.len());
+        for (slot, byte) in buf.iter_mut().zip(self.output.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for EmulatedSensor {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for packet in self.framer.push(buf) {
+            self.handle_packet(&packet);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// stable pseudo random template for a finger id (xorshift seeded by the id)
+fn features(finger: u32) -> Vec<u8> {
+    let mut state = finger.wrapping_mul(0x9E37_79B9) | 1;
+    (0..TEMPLATE_SIZE)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
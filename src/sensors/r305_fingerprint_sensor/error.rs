@@ -10,6 +10,28 @@ pub enum FingerprintError {
     #[error("Serial port error: {0}")]
     Serial(#[from] io::Error),
 
+    #[error("No reply to {command} before the port timed out (got {received:02X?})")]
+    Timeout { command: &'static str, received: Vec<u8> },
+
+    #[error("Sensor disconnected during {command}: {source}")]
+    Disconnected { command: &'static str, source: io::Error },
+
+    #[error("Checksum mismatch in reply to {command}: expected 0x{expected:04X}, got 0x{found:04X} in {received:02X?}")]
+    ChecksumMismatch { command: &'static str, expected: u16, found: u16, received: Vec<u8> },
+
+    #[error("Unexpected packet type 0x{packet_type:02X} in reply to {command}: {received:02X?}")]
+    UnexpectedPacketType { command: &'static str, packet_type: u8, received: Vec<u8> },
+
+    #[error("Malformed reply to {command}, {reason}: {received:02X?}")]
+    Malformed { command: &'static str, reason: String, received: Vec<u8> },
+
+    #[error("Reply to {command} came from address 0x{found:08X}, expected 0x{expected:08X}")]
+    AddressMismatch { command: &'static str, expected: u32, found: u32 },
+
+    #[cfg(feature = "rpi-gpio")]
+    #[error("GPIO error: {0}")]
+    Gpio(#[from] rppal::gpio::Error),
+
     #[error("Invalid password or communication error")]
     Auth,
 
@@ -19,6 +41,198 @@ pub enum FingerprintError {
     #[error("Sensor returned error code: 0x{0:02X}")]
     SensorError(u8),
 
+    #[error("{command} is not supported by this sensor ({model})")]
+    Unsupported { command: &'static str, model: String },
+
+    #[error("Template archive error: {0}")]
+    Archive(String),
+
+    #[error("Template page {page} has {size} templates but the sensor only holds {capacity}")]
+    PageTooLarge { page: String, size: usize, capacity: usize },
+
+    #[error("Slot {0} holds a template that isn't in any page, refusing to page over it")]
+    SlotInUse(u16),
+
+    #[error("No template page named {0}")]
+    UnknownPage(String),
+
+    #[error("Can't enroll while the sensor's templates are paged from the host")]
+    Paged,
+
     #[error("Reached Maximum Retry limit")]
     MaxRetries,
 }
+
+impl FingerprintError {
+    // whether trying the same operation again can succeed: line noise and finger
+    // placement problems are worth a retry, wiring, config and missing features are not
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            FingerprintError::Timeout { .. }
+            | FingerprintError::ChecksumMismatch { .. }
+            | FingerprintError::UnexpectedPacketType { .. }
+            | FingerprintError::Malformed { .. }
+            | FingerprintError::MaxRetries => true,
+            // communication error, no finger, bad image, too few features, no match, merge failed
+            FingerprintError::SensorError(code) => {
+                matches!(code, 0x01 | 0x02 | 0x03 | 0x06 | 0x07 | 0x08 | 0x0A)
+            }
+            _ => false,
+        }
+    }
+
+    // whether the sensor stopped answering properly, as opposed to answering with a problem
+    // such as no finger or no match
+    pub fn is_link_error(&self) -> bool {
+        matches!(
+            self,
+            FingerprintError::Serial(_)
+                | FingerprintError::Timeout { .. }
+                | FingerprintError::Disconnected { .. }
+                | FingerprintError::ChecksumMismatch { .. }
+                | FingerprintError::UnexpectedPacketType { .. }
+                | FingerprintError::Malformed { .. }
+                | FingerprintError::AddressMismatch { .. }
+        )
+    }
+
+    // sorts an io error from the transport into timeout, disconnect or plain serial error
+    pub(crate) fn from_io(command: &'static str, error: io::Error, received: &[u8]) -> Self {
+        match error.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                FingerprintError::Timeout { command, received: received.to_vec() }
+            }
+            io::ErrorKind::BrokenPipe
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::NotFound => FingerprintError::Disconnected { command, source: error },
+            _ => FingerprintError::Serial(error),
+        }
+    }
+}
+
+// the driver core reports port errors as they came, sorted the same way as before
+impl From<r305::Error<io::Error>> for FingerprintError {
+    fn from(error: r305::Error<io::Error>) -> Self {
+        match error {
+            r305::Error::Io { command, error, received } => FingerprintError::from_io(command, error, &received),
+            r305::Error::Closed { command, received } => {
+                FingerprintError::from_io(command, io::ErrorKind::UnexpectedEof.into(), &received)
+            }
+            r305::Error::ChecksumMismatch { command, expected, found, received } => {
+                FingerprintError::ChecksumMismatch { command, expected, found, received }
+            }
+            r305::Error::UnexpectedPacketType { command, packet_type, received } => {
+                FingerprintError::UnexpectedPacketType { command, packet_type, received }
+            }
+            r305::Error::Malformed { command, reason, received } => {
+                FingerprintError::Malformed { command, reason, received }
+            }
+            r305::Error::AddressMismatch { command, expected, found } => {
+                FingerprintError::AddressMismatch { command, expected, found }
+            }
+            r305::Error::Auth => FingerprintError::Auth,
+            r305::Error::Protocol(message) => FingerprintError::Protocol(message),
+            r305::Error::SensorError(code) => FingerprintError::SensorError(code),
+            r305::Error::Unsupported { command, model } => FingerprintError::Unsupported { command, model },
+            r305::Error::MaxRetries => FingerprintError::MaxRetries,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn malformed() -> FingerprintError {
+        FingerprintError::Malformed { command: "Search", reason: "too short".to_string(), received: vec![0xEF] }
+    }
+
+    #[test]
+    fn retryable_and_link_errors() {
+        // error, retryable, link error
+        let cases = [
+            (FingerprintError::Timeout { command: "Search", received: Vec::new() }, true, true),
+            (
+                FingerprintError::ChecksumMismatch { command: "Search", expected: 1, found: 2, received: Vec::new() },
+                true,
+                true,
+            ),
+            (
+                FingerprintError::UnexpectedPacketType { command: "Search", packet_type: 0x02, received: Vec::new() },
+                true,
+                true,
+            ),
+            (malformed(), true, true),
+            (FingerprintError::MaxRetries, true, false),
+            // no finger, bad image, no match
+            (FingerprintError::SensorError(0x02), true, false),
+            (FingerprintError::SensorError(0x06), true, false),
+            (FingerprintError::SensorError(0x08), true, false),
+            (FingerprintError::Serial(io::ErrorKind::PermissionDenied.into()), false, true),
+            (
+                FingerprintError::Disconnected { command: "Search", source: io::ErrorKind::BrokenPipe.into() },
+                false,
+                true,
+            ),
+            (FingerprintError::AddressMismatch { command: "Search", expected: 1, found: 2 }, false, true),
+            // bad slot number, flash write failed
+            (FingerprintError::SensorError(0x0B), false, false),
+            (FingerprintError::SensorError(0x18), false, false),
+            (FingerprintError::NoFingerprintSensors, false, false),
+            (FingerprintError::Auth, false, false),
+            (FingerprintError::Unsupported { command: "AuraLedConfig", model: "R305".to_string() }, false, false),
+            (FingerprintError::Archive("Not a template archive".to_string()), false, false),
+            (FingerprintError::SlotInUse(3), false, false),
+            (FingerprintError::Paged, false, false),
+        ];
+
+        for (error, retryable, link) in cases {
+            assert_eq!(error.is_retryable(), retryable, "is_retryable for {:?}", error);
+            assert_eq!(error.is_link_error(), link, "is_link_error for {:?}", error);
+        }
+    }
+
+    #[test]
+    fn io_errors_are_sorted_by_kind() {
+        let bytes = [0xEF, 0x01];
+        for kind in [io::ErrorKind::TimedOut, io::ErrorKind::WouldBlock] {
+            let error = FingerprintError::from_io("Search", kind.into(), &bytes);
+            assert!(matches!(&error, FingerprintError::Timeout { received, .. } if received == &bytes));
+            assert!(error.is_retryable());
+        }
+
+        let disconnects = [
+            io::ErrorKind::BrokenPipe,
+            io::ErrorKind::NotConnected,
+            io::ErrorKind::ConnectionReset,
+            io::ErrorKind::ConnectionAborted,
+            io::ErrorKind::UnexpectedEof,
+            io::ErrorKind::NotFound,
+        ];
+        for kind in disconnects {
+            let error = FingerprintError::from_io("Search", kind.into(), &bytes);
+            let disconnected = matches!(&error, FingerprintError::Disconnected { source, .. } if source.kind() == kind);
+            assert!(disconnected, "{:?}", kind);
+            assert!(!error.is_retryable());
+        }
+
+        for kind in [io::ErrorKind::PermissionDenied, io::ErrorKind::InvalidData, io::ErrorKind::Other] {
+            let error = FingerprintError::from_io("Search", kind.into(), &bytes);
+            assert!(matches!(&error, FingerprintError::Serial(e) if e.kind() == kind), "{:?}", kind);
+            assert!(!error.is_retryable());
+        }
+    }
+
+    #[test]
+    fn driver_core_errors_keep_their_class() {
+        let closed = FingerprintError::from(r305::Error::Closed { command: "Search", received: Vec::new() });
+        assert!(matches!(closed, FingerprintError::Disconnected { command: "Search", .. }));
+
+        let malformed = r305::Error::Malformed { command: "Search", reason: "too short".to_string(), received: vec![] };
+        assert!(FingerprintError::from(malformed).is_retryable());
+        assert!(!FingerprintError::from(r305::Error::<io::Error>::Auth).is_retryable());
+    }
+}
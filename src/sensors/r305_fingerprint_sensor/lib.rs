@@ -1,125 +1,168 @@
+use crate::sensors::r305_fingerprint_sensor::aura::AuraLedConfig;
+use crate::sensors::r305_fingerprint_sensor::capabilities::Capabilities;
 use crate::sensors::r305_fingerprint_sensor::error::FingerprintError;
 use crate::sensors::r305_fingerprint_sensor::protocol::*;
+use crate::sensors::r305_fingerprint_sensor::sharding::{ShardedLibrary, TemplatePage};
+#[cfg(feature = "rpi-gpio")]
+use crate::sensors::r305_fingerprint_sensor::touch::TouchPin;
+#[cfg(feature = "serial-sensor")]
+use crate::sensors::r305_fingerprint_sensor::transport::open_first_port;
+use crate::sensors::r305_fingerprint_sensor::transport::{StdDelay, StdSerial, Transport};
+
+use r305::FingerprintDriver;
+use std::time::{Duration, Instant};
+
+pub use r305::SearchMode;
+pub use r305::protocol::{ProductInfo, SystemParameters};
+
+// how long AutoEnroll/AutoIdentify may wait for a finger before giving up
+const AUTO_COMMAND_WAIT: Duration = Duration::from_secs(30);
+
+// how long a capture waits on the touch pin before giving up like GenImg retries do
+#[cfg(feature = "rpi-gpio")]
+const TOUCH_WAIT: Duration = Duration::from_secs(10);
+
+// where an enrollment is up to, for showing it to whoever is enrolling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnrollStage {
+    PlaceFinger,
+    PlaceAgain,
+    Merging,
+    Storing,
+    Done,
+}
 
-use serialport::{self, DataBits, ErrorKind, FlowControl, Parity, SerialPort, StopBits};
-use std::io::{self, Read, Write};
-use std::time::Duration;
-
-// view system info
-#[derive(Debug, PartialEq, Eq)]
-pub struct SystemParameters {
-    pub status_register: u16,
-    pub system_id: u16,
-    pub storage_capacity: u16,
-    pub security_level: u16,
-    pub device_address: u32,
-    pub packet_length: u16,
-    pub baud_rate: u16,
+// the stage plus how many of the enrollment's steps are done, steps differ between modules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnrollProgress {
+    pub stage: EnrollStage,
+    pub step: u32,
+    pub steps: u32,
 }
 
+// the no_std driver core plus what only a Pi has: a touch pin, sleeps and logging
 pub struct FingerprintSensor {
-    port: Box<dyn SerialPort>,
-    address: u32,
-    password: u32
+    driver: FingerprintDriver<StdSerial>,
+    #[cfg(feature = "rpi-gpio")]
+    touch_pin: Option<TouchPin>,
+    // templates paged in from the host, searched instead of just what's on the sensor
+    pages: Option<ShardedLibrary>,
+    // told about every finger on the sensor, e.g. to wake the screen
+    on_finger: Option<Box<dyn FnMut() + Send>>,
 }
 
 impl FingerprintSensor {
 
     //makes a new fingerprint sensor struct after verifying password
+    #[cfg(feature = "serial-sensor")]
     pub fn new(baud_rate: u32, address: u32, password: u32) -> Result<Self, FingerprintError> {
-        if baud_rate < 9600 || baud_rate > 57600 {
-            serialport::Error::new(ErrorKind::InvalidInput, "Baud rate must be between 9600 and 57600");
-        }
+        Self::with_transport(open_first_port(baud_rate)?, address, password)
+    }
 
-        let ports = serialport::available_ports().map_err(|_| FingerprintError::NoFingerprintSensors)?;
-        let port_name = if ports.is_empty() {
-            return Err(FingerprintError::NoFingerprintSensors);
-        } else {
-            ports.first().unwrap().port_name.clone()
+    // same as new but over any transport, e.g. a traced port or a replayed trace
+    pub fn with_transport(
+        port: Box<dyn Transport>,
+        address: u32,
+        password: u32,
+    ) -> Result<Self, FingerprintError> {
+        let sensor = FingerprintSensor {
+            driver: FingerprintDriver::new(StdSerial(port), address, password)?,
+            #[cfg(feature = "rpi-gpio")]
+            touch_pin: None,
+            pages: None,
+            on_finger: None,
         };
 
-        let port = serialport::new(&port_name, baud_rate)
-            .data_bits(DataBits::Eight)
-            .flow_control(FlowControl::None)
-            .parity(Parity::None)
-            .stop_bits(StopBits::One)
-            .timeout(Duration::from_secs(2))
-            .open()
-            .map_err(|_| FingerprintError::NoFingerprintSensors)?;
-
-        let mut sensor = FingerprintSensor { port, address, password };
-
-        match sensor.verify_password() {
-            Ok(_) => {
-                sensor.log("Sensor initialised and password verified", false);
-                Ok(sensor)
-            },
-            Err(_) => Err(FingerprintError::Auth)
-        }
+        sensor.log("Sensor initialised and password verified", false);
+        sensor.log(&format!("Detected capabilities: {:?}", sensor.capabilities()), false);
+        Ok(sensor)
     }
 
-    pub fn get_system_parameters(&mut self) -> Result<SystemParameters, FingerprintError> {
-        let command_payload = vec![FINGERPRINT_GET_SYSTEM_PARAMETERS];
+    pub fn capabilities(&self) -> &Capabilities {
+        self.driver.capabilities()
+    }
 
-        let command_packet = self.build_packet(COMMAND_PACKET, &command_payload);
-        self.send_command(&command_packet)?;
+    // system parameters read when the sensor was opened
+    pub fn parameters(&self) -> &SystemParameters {
+        self.driver.parameters()
+    }
 
-        let full = self.receive_exact()?;
-        let (received_packet_type, received_payload) = self.parse_response_packet(&full)?;
+    // waits on the touch pin's interrupt before capturing instead of polling GenImg
+    #[cfg(feature = "rpi-gpio")]
+    pub fn set_touch_pin(&mut self, touch_pin: TouchPin) {
+        self.touch_pin = Some(touch_pin);
+        self.log("Using touch pin for finger detection", false);
+    }
 
+    // called as soon as a finger is on the sensor: when the touch pin fires, otherwise once
+    // the sensor has an image of it, so before a search or enroll step has finished
+    pub fn set_on_finger(&mut self, on_finger: impl FnMut() + Send + 'static) {
+        self.on_finger = Some(Box::new(on_finger));
+    }
 
-        if received_packet_type != ACK_PACKET {
-            return Err(FingerprintError::Protocol("Recieved packet is not ack packet!".to_string()));
-        }
+    // searches every page from now on, paging them onto the top of the sensor's library as
+    // needed. Templates enrolled below that are kept but not searched, and enrolling is refused
+    pub fn set_pages(&mut self, pages: Vec<TemplatePage>) -> Result<(), FingerprintError> {
+        let count = pages.len();
+        self.pages = Some(ShardedLibrary::new(pages, self)?);
+        self.log(&format!("Searching {} template pages", count), false);
+        Ok(())
+    }
 
-        if received_payload.is_empty() || received_payload[0] != FINGERPRINT_OK {
-            return Err(FingerprintError::Protocol("Received payload is not OK".to_string()));
-        }
+    pub fn is_paged(&self) -> bool {
+        self.pages.is_some()
+    }
 
-        if received_payload.len() < 17 {
-            return Err(FingerprintError::Protocol("Received payload too short".to_string()));
-        }
+    // loads a page ahead of its first search, e.g. at shift change
+    pub fn preload_page(&mut self, name: &str) -> Result<(), FingerprintError> {
+        self.with_pages(|pages, sensor| pages.preload(sensor, name))
+            .unwrap_or_else(|| Err(FingerprintError::UnknownPage(name.to_string())))
+    }
 
-        let status_register = u16::from_be_bytes([received_payload[1], received_payload[2]]);
-        let system_id = u16::from_be_bytes([received_payload[3], received_payload[4]]);
-        let storage_capacity = u16::from_be_bytes([received_payload[5], received_payload[6]]);
-        let security_level = u16::from_be_bytes([received_payload[7], received_payload[8]]);
-
-        let device_address = u32::from_be_bytes([
-            received_payload[9],
-            received_payload[10],
-            received_payload[11],
-            received_payload[12],
-        ]);
-
-        let packet_length = u16::from_be_bytes([received_payload[13], received_payload[14]]);
-        let baud_rate = u16::from_be_bytes([received_payload[15], received_payload[16]]);
-
-        Ok(SystemParameters {
-            status_register,
-            system_id,
-            storage_capacity,
-            security_level,
-            device_address,
-            packet_length,
-            baud_rate,
-        })
-    }
-
-    // enrolls a new fingerprint
-    pub fn enroll(&mut self, id: u16) -> Result<(), FingerprintError> {
-        self.get_image()?;
-        self.image2tz(1)?;
+    // sends any command and decodes its acknowledgement, for flows the helpers don't cover
+    pub fn execute(&mut self, command: Command) -> Result<Response, FingerprintError> {
+        Ok(self.driver.execute(command)?)
+    }
 
-        self.log("Remove Finger...", false);
-        std::thread::sleep(Duration::from_secs(1));
+    // reads product info, fails with Unsupported on modules without ReadProdInfo
+    pub fn get_product_info(&mut self) -> Result<ProductInfo, FingerprintError> {
+        Ok(self.driver.get_product_info()?)
+    }
 
-        self.log("Place the same finger again...", false);
-        self.get_image()?;
-        self.image2tz(2)?;
+    // sets the aura ring LED, fails with Unsupported on modules without one
+    pub fn set_aura_led(&mut self, config: AuraLedConfig) -> Result<(), FingerprintError> {
+        Ok(self.driver.set_aura_led(config)?)
+    }
+
+    pub fn search_mode(&self) -> SearchMode {
+        self.driver.search_mode()
+    }
+
+    // picks the search instruction, HighSpeed is already the default where supported
+    pub fn set_search_mode(&mut self, mode: SearchMode) -> Result<(), FingerprintError> {
+        Ok(self.driver.set_search_mode(mode)?)
+    }
+
+    pub fn get_system_parameters(&mut self) -> Result<SystemParameters, FingerprintError> {
+        Ok(self.driver.get_system_parameters()?)
+    }
+
+    // enrolls a new fingerprint, on the module itself when it supports AutoEnroll
+    pub fn enroll(&mut self, id: u16) -> Result<(), FingerprintError> {
+        self.enroll_with(id, |_| {})
+    }
+
+    // same as enroll, calling on_progress as each step starts and once more when it's done
+    pub fn enroll_with(&mut self, id: u16, mut on_progress: impl FnMut(EnrollProgress)) -> Result<(), FingerprintError> {
+        if self.is_paged() {
+            return Err(FingerprintError::Paged);
+        }
 
-        self.create_model()?;
-        self.store_model(id)?;
+        if self.capabilities().auto_enroll {
+            self.auto_enroll(id, &mut on_progress)?;
+        } else {
+            self.manual_enroll(id, &mut on_progress)?;
+        }
 
         self.log(&format!("Fingerprint enrolled at : {}", id), false);
         Ok(())
@@ -127,258 +170,272 @@ impl FingerprintSensor {
 
     // drops all stored templates
     pub fn delete_all(&mut self) -> Result<(), FingerprintError> {
-        let packet = self.build_packet(COMMAND_PACKET, &[FINGERPRINT_CLEAR_DATABASE]);
-        self.send_command(&packet)?;
-        let response = self.receive_exact()?;
-        let (_, payload) = self.parse_response_packet(&response)?;
-        self.expect_ok(&payload)?;
+        self.driver.delete_all()?;
         self.log("Fingerprint Database deleted", false);
         Ok(())
     }
 
-    // searches for the fingerprint
+    // searches for the fingerprint, on the module itself when it supports AutoIdentify.
+    // with pages set every page is searched and the template id is returned, not the slot
     pub fn search(&mut self) -> Result<Option<u16>, FingerprintError> {
-        self.get_image()?;
-        self.image2tz(1)?;
-
-        let payload = [
-            FINGERPRINT_SEARCH_TEMPLATE,
-            0x01,
-            0x00, 0x00,
-            0x00, 0xA3
-        ];
-        let packet = self.build_packet(COMMAND_PACKET, &payload);
-        self.send_command(&packet)?;
-        let response = self.receive_exact()?;
-        let (_, payload) = self.parse_response_packet(&response)?;
-
-        if payload[0] == FINGERPRINT_OK {
-            let id = u16::from_be_bytes([payload[1], payload[2]]);
-            let _score = u16::from_be_bytes([payload[3], payload[4]]);
-            Ok(Some(id))
-        } else if payload[0] == FINGERPRINT_ERROR_NO_TEMPLATE_FOUND {
-            Ok(None)
+        if let Some(result) = self.with_pages(|pages, sensor| pages.search(sensor)) {
+            return result;
+        }
+        if self.capabilities().auto_identify {
+            self.auto_identify()
         } else {
-            Err(FingerprintError::SensorError(payload[0]))
+            self.manual_search()
         }
     }
 
+    // low level building blocks, for flows like verify-then-update that enroll/search don't cover
 
-    // util to unwrap payload
-    fn expect_ok(&self, payload: &[u8]) -> Result<(), FingerprintError> {
-        match payload.get(0) {
-            Some(&FINGERPRINT_OK) => Ok(()),
-            Some(code) => Err(FingerprintError::SensorError(*code)),
-            None => Err(FingerprintError::Protocol("Got empty payload".to_string())),
+    // captures a finger into the image buffer, retrying while no finger is on the sensor
+    pub fn capture_image(&mut self) -> Result<(), FingerprintError> {
+        let touched = self.wait_for_finger()?;
+        self.driver.capture_image(&mut StdDelay)?;
+        if !touched {
+            self.finger_detected();
         }
+        self.log("Image Captured", false);
+        Ok(())
     }
 
-    // converts image to template
-    fn image2tz(&mut self, slot: u8) -> Result<(), FingerprintError> {
-        let payload = [FINGERPRINT_CONVERT_IMAGE, slot];
-        let packet = self.build_packet(COMMAND_PACKET, &payload);
-        self.send_command(&packet)?;
-        let response = self.receive_exact()?;
-        let (_, payload) = self.parse_response_packet(&response)?;
-        self.expect_ok(&payload)?;
-        Ok(())
+    // generates features from the image buffer into a char buffer
+    pub fn generate_char(&mut self, buffer: CharBuffer) -> Result<(), FingerprintError> {
+        Ok(self.driver.generate_char(buffer)?)
     }
 
-    // reads image from sensor
-    fn get_image(&mut self) -> Result<(), FingerprintError> {
-        let max_retries = 10;
-        for retry_no in 0..max_retries {
-            let packet = self.build_packet(COMMAND_PACKET, &[FINGERPRINT_READ_IMAGE]);
-            self.send_command(&packet)?;
-            let response = self.receive_exact()?;
-            let (_, payload) = self.parse_response_packet(&response)?;
+    // merges both char buffers into one template, left in both buffers
+    pub fn merge_chars(&mut self) -> Result<(), FingerprintError> {
+        Ok(self.driver.merge_chars()?)
+    }
 
-            match payload.get(0) {
-                Some(&FINGERPRINT_OK) => {
-                    self.log("Image Captured", false);
-                    return Ok(());
-                }
-                Some(&0x02) => {
-                    self.log(&format!("No finger detected. Retry no: {}/10", retry_no), true);
-                    std::thread::sleep(Duration::from_millis(500));
-                    continue;
-                }
-                Some(&code) => return Err(FingerprintError::SensorError(code)),
-                None => return Err(FingerprintError::Protocol("Got empty payload".to_string())),
-            }
-        }
+    // stores a char buffer into a library slot
+    pub fn store_char(&mut self, buffer: CharBuffer, id: u16) -> Result<(), FingerprintError> {
+        Ok(self.driver.store_char(buffer, id)?)
+    }
 
-        Err(FingerprintError::MaxRetries)
+    // loads a stored template from a library slot into a char buffer
+    pub fn load_char(&mut self, buffer: CharBuffer, id: u16) -> Result<(), FingerprintError> {
+        Ok(self.driver.load_char(buffer, id)?)
     }
 
-    // creates template
-    fn create_model(&mut self) -> Result<(), FingerprintError> {
-        let packet = self.build_packet(COMMAND_PACKET, &[FINGERPRINT_CREATE_TEMPLATE]);
-        self.send_command(&packet)?;
-        let response = self.receive_exact()?;
-        let (_, payload) = self.parse_response_packet(&response)?;
-        self.expect_ok(&payload)?;
-        Ok(())
+    // compares CharBuffer1 against CharBuffer2, the score if they match
+    pub fn match_chars(&mut self) -> Result<Option<u16>, FingerprintError> {
+        Ok(self.driver.match_chars()?)
     }
 
-    // stores template
-    fn store_model(&mut self, id: u16) -> Result<(), FingerprintError> {
-        let id_bytes = id.to_be_bytes();
-        let payload = [FINGERPRINT_STORE_TEMPLATE, 0x01, id_bytes[0], id_bytes[1]];
-        let packet = self.build_packet(COMMAND_PACKET, &payload);
-        self.send_command(&packet)?;
-        let response = self.receive_exact()?;
-        let (_, payload) = self.parse_response_packet(&response)?;
-        self.expect_ok(&payload)?;
-        Ok(())
+    // searches a range of library slots for the template in a char buffer, (slot, score) on a hit
+    pub fn search_char(
+        &mut self,
+        buffer: CharBuffer,
+        start: u16,
+        count: u16,
+    ) -> Result<Option<(u16, u16)>, FingerprintError> {
+        Ok(self.driver.search_char(buffer, start, count)?)
     }
 
-    //verifies password
-    fn verify_password(&mut self) -> Result<bool, FingerprintError> {
-        let password_bytes = self.password.to_be_bytes();
+    // reads a char buffer out of the module as raw template bytes
+    pub fn upload_char(&mut self, buffer: CharBuffer) -> Result<Vec<u8>, FingerprintError> {
+        Ok(self.driver.upload_char(buffer)?)
+    }
 
-        let command_payload = vec![
-            FINGERPRINT_VERIFY_PASSWORD,
-            password_bytes[0],
-            password_bytes[1],
-            password_bytes[2],
-            password_bytes[3],
-        ];
+    // writes raw template bytes into a char buffer
+    pub fn download_char(&mut self, buffer: CharBuffer, data: &[u8]) -> Result<(), FingerprintError> {
+        Ok(self.driver.download_char(buffer, data)?)
+    }
 
-        let command_packet = self.build_packet(COMMAND_PACKET, &command_payload);
+    // raw bytes of the template stored in a library slot
+    pub fn upload_template(&mut self, id: u16) -> Result<Vec<u8>, FingerprintError> {
+        Ok(self.driver.upload_template(id)?)
+    }
 
-        self.send_command(&command_packet)?;
+    // stores raw template bytes into a library slot
+    pub fn download_template(&mut self, id: u16, data: &[u8]) -> Result<(), FingerprintError> {
+        Ok(self.driver.download_template(id, data)?)
+    }
 
-        let mut response_buffer = vec![0; 32];
-        let bytes_read = self.read_response(&mut response_buffer)?;
+    pub fn template_count(&mut self) -> Result<u16, FingerprintError> {
+        Ok(self.driver.template_count()?)
+    }
 
-        if bytes_read == 0 {
-            return Err(FingerprintError::Protocol("Got empty response".to_string()));
-        }
+    // every library slot that holds a template, walking the index table pages
+    pub fn occupied_slots(&mut self) -> Result<Vec<u16>, FingerprintError> {
+        Ok(self.driver.occupied_slots()?)
+    }
 
-        let (received_packet_type, received_payload) = self.parse_response_packet(&response_buffer[..bytes_read])?;
+    // capture, convert, merge and store one step at a time from the host
+    fn manual_enroll(&mut self, id: u16, on_progress: &mut impl FnMut(EnrollProgress)) -> Result<(), FingerprintError> {
+        let steps = 4;
+        let mut report = |stage, step| on_progress(EnrollProgress { stage, step, steps });
 
-        if received_packet_type != ACK_PACKET {
-            return Err(FingerprintError::Protocol("Got wrong ACK packet".to_string()));
-        }
+        report(EnrollStage::PlaceFinger, 0);
+        self.capture_image()?;
+        self.generate_char(CharBuffer::One)?;
 
-        if received_payload.is_empty() {
-            return Err(FingerprintError::Protocol("Got empty payload".to_string()));
-        }
+        self.log("Remove Finger...", false);
+        self.wait_for_release()?;
 
-        match received_payload[0] {
-            FINGERPRINT_OK => {
-                self.log("Password Verified", false);
-                Ok(true)
-            },
-            FINGERPRINT_ERROR_WRONG_PASSWORD => {
-                self.log("Invalid Password", true);
-                Ok(false)
-            }
-            FINGERPRINT_ERROR_COMMUNICATION => {
-                Err(FingerprintError::Protocol("Communication failed".to_string()))
-            },
-            FINGERPRINT_ADDR_CODE => {
-                Err(FingerprintError::Auth)
-            }
-            other => Err(FingerprintError::Protocol(format!("Received unknown payload: {:?}", other))),
-        }
+        self.log("Place the same finger again...", false);
+        report(EnrollStage::PlaceAgain, 1);
+        self.capture_image()?;
+        self.generate_char(CharBuffer::Two)?;
+
+        report(EnrollStage::Merging, 2);
+        self.merge_chars()?;
+        report(EnrollStage::Storing, 3);
+        self.store_char(CharBuffer::One, id)?;
+        report(EnrollStage::Done, steps);
+        Ok(())
     }
 
-    //helper to send packet to sensor
-    fn send_command(&mut self, command: &[u8]) -> io::Result<()> {
-        self.port.write_all(command)?;
-        self.port.flush()?;
+    // the module runs the whole enroll sequence and reports each step
+    fn auto_enroll(&mut self, id: u16, on_progress: &mut impl FnMut(EnrollProgress)) -> Result<(), FingerprintError> {
+        // one step per capture, then merging, storing and done
+        let captures = AUTO_ENROLL_CAPTURES as u32;
+        let steps = captures + 2;
+        let mut report = |stage, step| on_progress(EnrollProgress { stage, step, steps });
+
+        report(EnrollStage::PlaceFinger, 0);
+        self.wait_for_finger()?;
+
+        let started = Instant::now();
+        self.driver.auto_enroll(
+            id,
+            || started.elapsed() < AUTO_COMMAND_WAIT,
+            |step, detail| {
+                Self::log_step("AutoEnroll", step, detail);
+                // 0x02 is a capture turned into features, 0x04 the merge and 0x06 the store
+                match (step, detail as u32) {
+                    (0x02, capture) if capture < captures => report(EnrollStage::PlaceAgain, capture),
+                    (0x02, _) => report(EnrollStage::Merging, captures),
+                    (0x04, _) => report(EnrollStage::Storing, captures + 1),
+                    (0x06, _) => report(EnrollStage::Done, steps),
+                    _ => {}
+                }
+            },
+        )?;
         Ok(())
     }
 
-    //recieve exact no of bytes
-    fn receive_exact(&mut self) -> Result<Vec<u8>, FingerprintError> {
-        let mut header = [0u8; 9];
-        self.port.read_exact(&mut header)?;
+    // the pages are taken out for the call so they can drive the sensor, None without pages
+    fn with_pages<T>(
+        &mut self,
+        call: impl FnOnce(&mut ShardedLibrary, &mut Self) -> Result<T, FingerprintError>,
+    ) -> Option<Result<T, FingerprintError>> {
+        let mut pages = self.pages.take()?;
+        let result = call(&mut pages, self);
+        self.pages = Some(pages);
+        Some(result)
+    }
 
-        let length = u16::from_be_bytes([header[7], header[8]]) as usize;
-        let mut body = vec![0u8; length];
-        self.port.read_exact(&mut body)?;
+    fn manual_search(&mut self) -> Result<Option<u16>, FingerprintError> {
+        self.capture_image()?;
+        self.generate_char(CharBuffer::One)?;
 
-        let mut full = header.to_vec();
-        full.extend(body);
-        Ok(full)
+        // the whole library, enrolling hands out every slot up to the capacity
+        let capacity = self.parameters().storage_capacity;
+        let result = self.search_char(CharBuffer::One, 0x0000, capacity)?;
+        Ok(result.map(|(id, _)| id))
     }
 
-    //helper to read resposne from sensor
-    fn read_response(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
-        buffer.fill(0);
-        let bytes_read = self.port.read(buffer)?;
-        Ok(bytes_read)
+    // the module captures and searches the whole library in one command
+    fn auto_identify(&mut self) -> Result<Option<u16>, FingerprintError> {
+        let touched = self.wait_for_finger()?;
+
+        let started = Instant::now();
+        let on_finger = &mut self.on_finger;
+        Ok(self.driver.auto_identify(
+            || started.elapsed() < AUTO_COMMAND_WAIT,
+            |step, detail| {
+                Self::log_step("AutoIdentify", step, detail);
+                if let (AUTO_STEP_GET_IMAGE, false, Some(on_finger)) = (step, touched, on_finger.as_mut()) {
+                    on_finger();
+                }
+            },
+        )?)
     }
 
-    //calculates checksum for the packet
-    fn calculate_checksum(packet_type: u8, payload: &[u8]) -> u16 {
-        let mut sum: u16 = packet_type as u16;
-        sum = sum.wrapping_add((payload.len() + 2) as u16);
-
-        for &byte in payload {
-            sum = sum.wrapping_add(byte as u16);
+    // blocks until the touch pin fires, no-op without one. True when the pin saw the finger,
+    // the finger is reported then
+    #[cfg(feature = "rpi-gpio")]
+    fn wait_for_finger(&mut self) -> Result<bool, FingerprintError> {
+        let Some(touch_pin) = self.touch_pin.as_mut() else {
+            return Ok(false);
+        };
+        if !touch_pin.wait_for_finger(Some(TOUCH_WAIT))? {
+            return Err(FingerprintError::MaxRetries);
         }
-        sum
+        self.finger_detected();
+        Ok(true)
     }
 
-    //builds the packet
-    fn build_packet(&self, packet_type: u8, payload: &[u8]) -> Vec<u8> {
-        let mut packet = Vec::new();
-        packet.push(PACKET_START_CODE_1);
-        packet.push(PACKET_START_CODE_2);
-        packet.extend_from_slice(&self.address.to_be_bytes());
-        packet.push(packet_type);
-        let packet_length = (payload.len() + 2) as u16;
-        packet.extend_from_slice(&packet_length.to_be_bytes());
-        packet.extend_from_slice(payload);
-        let checksum = Self::calculate_checksum(packet_type, payload);
-        packet.extend_from_slice(&checksum.to_be_bytes());
-
-        packet
+    #[cfg(not(feature = "rpi-gpio"))]
+    fn wait_for_finger(&mut self) -> Result<bool, FingerprintError> {
+        Ok(false)
     }
 
-    //parses packet response
-    fn parse_response_packet(&self, raw_bytes: &[u8]) -> Result<(u8, Vec<u8>), FingerprintError> {
-        if raw_bytes.len() < 12 {
-            return Err(FingerprintError::Protocol("Received Packet too short".to_string()));
-        }
-
-        if raw_bytes[0] != PACKET_START_CODE_1 || raw_bytes[1] != PACKET_START_CODE_2 {
-            return Err(FingerprintError::Protocol("Invalid start code in response".to_string()));
+    fn finger_detected(&mut self) {
+        if let Some(on_finger) = self.on_finger.as_mut() {
+            on_finger();
         }
+    }
 
-        let received_address = u32::from_be_bytes([raw_bytes[2], raw_bytes[3], raw_bytes[4], raw_bytes[5]]);
-        if received_address != self.address {
-            return Err(FingerprintError::Auth);
+    // gives the finger time to lift between the two enroll captures
+    #[cfg(feature = "rpi-gpio")]
+    fn wait_for_release(&mut self) -> Result<(), FingerprintError> {
+        match self.touch_pin.as_mut() {
+            Some(touch_pin) => {
+                touch_pin.wait_for_release(Some(TOUCH_WAIT))?;
+            }
+            None => std::thread::sleep(Duration::from_secs(1)),
         }
+        Ok(())
+    }
 
-        let packet_type = raw_bytes[6];
-        let packet_length = u16::from_be_bytes([raw_bytes[7], raw_bytes[8]]);
-
-        let expected_payload_len = (packet_length - 2) as usize;
+    #[cfg(not(feature = "rpi-gpio"))]
+    fn wait_for_release(&mut self) -> Result<(), FingerprintError> {
+        std::thread::sleep(Duration::from_secs(1));
+        Ok(())
+    }
 
-        if raw_bytes.len() < 9 + expected_payload_len + 2 {
-            return Err(FingerprintError::Protocol("Received packet too short".to_string()));
-        }
+    fn log_step(command: &str, step: u8, detail: u8) {
+        println!("[INFO] FingerprintSensor: {} step 0x{:02X} done (0x{:02X})", command, step, detail);
+    }
 
-        let payload_start_idx = 9;
-        let payload_end_idx = payload_start_idx + expected_payload_len;
-        let payload = raw_bytes[payload_start_idx..payload_end_idx].to_vec();
+    pub(crate) fn log(&self, message: &str, warning: bool) {
+        println!("{} FingerprintSensor: {}", if warning { "[WARNING]" } else { "[INFO]" }, message);
+    }
+}
 
-        let received_checksum = u16::from_be_bytes([raw_bytes[payload_end_idx], raw_bytes[payload_end_idx + 1]]);
-        let calculated_checksum = Self::calculate_checksum(packet_type, &payload);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::r305_fingerprint_sensor::emulator::{EmulatedModel, EmulatedSensor, SearchTiming};
+
+    // a ZFM module, so searches go through the manual capture and search flow
+    fn emulated(capacity: u16, enrolled: u16, finger: u32) -> FingerprintSensor {
+        let timing = SearchTiming { normal: Duration::ZERO, high_speed: Duration::ZERO };
+        let mut emulator = EmulatedSensor::new(EmulatedModel::R305, 0xFFFFFFFF, 0, capacity).with_timing(timing);
+        emulator.fill_library(enrolled);
+        emulator.handle().place_finger(finger);
+        FingerprintSensor::with_transport(Box::new(emulator), 0xFFFFFFFF, 0).unwrap()
+    }
 
-        if received_checksum != calculated_checksum {
-            return Err(FingerprintError::Protocol("Checksum mismatch".to_string()));
-        }
+    #[test]
+    fn search_finds_fingers_past_slot_163() {
+        assert_eq!(emulated(1000, 300, 250).search().unwrap(), Some(250));
+        assert_eq!(emulated(1000, 300, 299).search().unwrap(), Some(299));
+    }
 
-        Ok((packet_type, payload))
+    #[test]
+    fn search_still_finds_low_slots() {
+        assert_eq!(emulated(1000, 300, 10).search().unwrap(), Some(10));
     }
 
-    fn log(&self, message: &str, warning: bool) {
-        println!("{} FingerprintSensor: {}", if warning { "[WARNING]" } else { "[INFO]" }, message);
+    #[test]
+    fn search_misses_fingers_that_are_not_enrolled() {
+        assert_eq!(emulated(1000, 300, 500).search().unwrap(), None);
     }
-}
\ No newline at end of file
+}
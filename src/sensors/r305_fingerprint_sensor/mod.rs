@@ -1,3 +1,13 @@
 pub mod lib;
-mod protocol;
-mod error;
\ No newline at end of file
+pub mod backup;
+#[cfg(any(test, feature = "simulator"))]
+pub mod emulator;
+#[cfg(feature = "rpi-gpio")]
+pub mod touch;
+pub mod trace;
+pub mod transport;
+pub mod sharding;
+pub mod error;
+
+// codec, capabilities and command logic live in the no_std r305 crate
+pub use r305::{aura, capabilities, protocol};
@@ -0,0 +1,220 @@
+use crate::sensors::r305_fingerprint_sensor::backup::{ArchivedTemplate, TemplateArchive};
+use crate::sensors::r305_fingerprint_sensor::error::FingerprintError;
+use crate::sensors::r305_fingerprint_sensor::lib::FingerprintSensor;
+use crate::sensors::r305_fingerprint_sensor::protocol::CharBuffer;
+
+// a cohort of templates held on the host, e.g. one department or one shift.
+// template ids are library-wide, the sensor slot is just the position in the page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplatePage {
+    pub name: String,
+    pub templates: Vec<ArchivedTemplate>,
+}
+
+impl TemplatePage {
+    pub fn from_archive(name: &str, archive: TemplateArchive) -> Self {
+        TemplatePage { name: name.to_string(), templates: archive.templates }
+    }
+}
+
+// a template library bigger than the sensor, paged onto it one cohort at a time.
+// attached to a sensor with FingerprintSensor::set_pages, whose search then covers every page.
+// pages go into a window at the top of the sensor's library, as big as the largest page, and
+// templates enrolled on the sensor below it are never touched
+#[derive(Debug, Clone)]
+pub struct ShardedLibrary {
+    pages: Vec<TemplatePage>,
+    // first sensor slot of the window
+    first: u16,
+    // the template id in each slot of the window, None where it isn't known, e.g. after a
+    // write that failed half way
+    slots: Vec<Option<u16>>,
+    loaded: Option<usize>,
+}
+
+impl ShardedLibrary {
+    // reads back what a previous run left in the window so it isn't written again, and
+    // refuses to page over a template that isn't in any page, e.g. a user enrolled there
+    pub fn new(pages: Vec<TemplatePage>, sensor: &mut FingerprintSensor) -> Result<Self, FingerprintError> {
+        let capacity = sensor.parameters().storage_capacity as usize;
+        if let Some(page) = pages.iter().find(|page| page.templates.len() > capacity) {
+            return Err(FingerprintError::PageTooLarge {
+                page: page.name.clone(),
+                size: page.templates.len(),
+                capacity,
+            });
+        }
+
+        let size = pages.iter().map(|page| page.templates.len()).max().unwrap_or(0);
+        let first = (capacity - size) as u16;
+        let mut slots = vec![None; size];
+
+        for slot in sensor.occupied_slots()?.into_iter().filter(|&slot| slot >= first) {
+            sensor.load_char(CharBuffer::Two, slot)?;
+            let data = sensor.upload_char(CharBuffer::Two)?;
+            let template = pages.iter().flat_map(|page| &page.templates).find(|template| template.data == data);
+            match template {
+                Some(template) => slots[(slot - first) as usize] = Some(template.id),
+                None => return Err(FingerprintError::SlotInUse(slot)),
+            }
+        }
+
+        Ok(ShardedLibrary { pages, first, slots, loaded: None })
+    }
+
+    pub fn loaded_page(&self) -> Option<&str> {
+        self.loaded.map(|index| self.pages[index].name.as_str())
+    }
+
+    // loads a cohort ahead of time, e.g. at shift change, so its first search is fast
+    pub fn preload(&mut self, sensor: &mut FingerprintSensor, name: &str) -> Result<(), FingerprintError> {
+        let index = self
+            .pages
+            .iter()
+            .position(|page| page.name == name)
+            .ok_or_else(|| FingerprintError::UnknownPage(name.to_string()))?;
+        self.load_page(sensor, index)
+    }
+
+    // same contract as FingerprintSensor::search but over every page: the loaded page is
+    // searched first, then the others are swapped in until one matches
+    pub fn search(&mut self, sensor: &mut FingerprintSensor) -> Result<Option<u16>, FingerprintError> {
+        if self.pages.is_empty() {
+            return Ok(None);
+        }
+
+        // one capture for all pages, pages are downloaded through buffer two to keep it
+        sensor.capture_image()?;
+        sensor.generate_char(CharBuffer::One)?;
+
+        let first = self.loaded.unwrap_or(0);
+        let order = (first..self.pages.len()).chain(0..first);
+
+        for index in order {
+            if self.loaded != Some(index) {
+                self.load_page(sensor, index)?;
+            }
+            if let Some(id) = self.search_loaded(sensor)? {
+                return Ok(Some(id));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn search_loaded(&mut self, sensor: &mut FingerprintSensor) -> Result<Option<u16>, FingerprintError> {
+        let Some(index) = self.loaded else {
+            return Ok(None);
+        };
+        let count = self.pages[index].templates.len() as u16;
+        if count == 0 {
+            return Ok(None);
+        }
+
+        let hit = sensor.search_char(CharBuffer::One, self.first, count)?;
+        let templates = &self.pages[index].templates;
+        let template = hit.and_then(|(slot, _)| templates.get(slot.checked_sub(self.first)? as usize));
+        Ok(template.map(|template| template.id))
+    }
+
+    // writes only the window slots whose template differs from the page's, slots past the end
+    // of the page are left as they are since searches never reach them
+    fn load_page(&mut self, sensor: &mut FingerprintSensor, index: usize) -> Result<(), FingerprintError> {
+        // a half loaded page must never be searched as if it was complete
+        self.loaded = None;
+
+        let page = &self.pages[index];
+        let mut written = 0;
+        for (slot, template) in page.templates.iter().enumerate() {
+            if self.slots[slot] == Some(template.id) {
+                continue;
+            }
+            self.slots[slot] = None;
+            sensor.download_char(CharBuffer::Two, &template.data)?;
+            sensor.store_char(CharBuffer::Two, self.first + slot as u16)?;
+            self.slots[slot] = Some(template.id);
+            written += 1;
+        }
+
+        let message = format!(
+            "Loaded template page {} ({} templates, {} written)",
+            page.name,
+            page.templates.len(),
+            written
+        );
+        sensor.log(&message, false);
+        self.loaded = Some(index);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::r305_fingerprint_sensor::emulator::{
+        EmulatedModel, EmulatedSensor, EmulatorHandle, SearchTiming,
+    };
+    use std::ops::Range;
+    use std::time::Duration;
+
+    fn emulated(capacity: u16, enrolled: u16) -> (FingerprintSensor, EmulatorHandle) {
+        let timing = SearchTiming { normal: Duration::ZERO, high_speed: Duration::ZERO };
+        let mut emulator = EmulatedSensor::new(EmulatedModel::R305, 0xFFFFFFFF, 0, capacity).with_timing(timing);
+        emulator.fill_library(enrolled);
+        let handle = emulator.handle();
+        (FingerprintSensor::with_transport(Box::new(emulator), 0xFFFFFFFF, 0).unwrap(), handle)
+    }
+
+    // the templates of the given fingers, read back from a sensor they are enrolled on
+    fn page(name: &str, fingers: Range<u16>) -> TemplatePage {
+        let (mut sensor, _) = emulated(fingers.end, fingers.end);
+        let templates = fingers
+            .map(|finger| {
+                sensor.load_char(CharBuffer::One, finger).unwrap();
+                ArchivedTemplate { id: 1000 + finger, data: sensor.upload_char(CharBuffer::One).unwrap() }
+            })
+            .collect();
+        TemplatePage { name: name.to_string(), templates }
+    }
+
+    #[test]
+    fn pages_go_above_enrolled_templates() {
+        let (mut sensor, handle) = emulated(20, 5);
+        let mut library = ShardedLibrary::new(vec![page("a", 10..13), page("b", 13..17)], &mut sensor).unwrap();
+        assert_eq!(library.first, 16);
+
+        handle.place_finger(14);
+        assert_eq!(library.search(&mut sensor).unwrap(), Some(1014));
+        assert_eq!(library.loaded_page(), Some("b"));
+        handle.place_finger(11);
+        assert_eq!(library.search(&mut sensor).unwrap(), Some(1011));
+
+        // what was enrolled is still there, only the window was written
+        let occupied = sensor.occupied_slots().unwrap();
+        assert_eq!(occupied, [0, 1, 2, 3, 4, 16, 17, 18, 19]);
+    }
+
+    #[test]
+    fn templates_left_by_an_earlier_run_are_not_written_again() {
+        let (mut sensor, _) = emulated(20, 5);
+        let pages = vec![page("a", 10..14)];
+        ShardedLibrary::new(pages.clone(), &mut sensor).unwrap().preload(&mut sensor, "a").unwrap();
+
+        let library = ShardedLibrary::new(pages, &mut sensor).unwrap();
+        assert_eq!(library.slots, [Some(1010), Some(1011), Some(1012), Some(1013)]);
+    }
+
+    #[test]
+    fn refuses_to_page_over_templates_that_are_not_in_a_page() {
+        let (mut sensor, _) = emulated(10, 10);
+        let refused = ShardedLibrary::new(vec![page("a", 20..22)], &mut sensor);
+        assert!(matches!(refused, Err(FingerprintError::SlotInUse(8))));
+    }
+
+    #[test]
+    fn refuses_pages_bigger_than_the_sensor() {
+        let (mut sensor, _) = emulated(3, 0);
+        let refused = ShardedLibrary::new(vec![page("a", 0..4)], &mut sensor);
+        assert!(matches!(refused, Err(FingerprintError::PageTooLarge { size: 4, capacity: 3, .. })));
+    }
+}
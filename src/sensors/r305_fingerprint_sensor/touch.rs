@@ -0,0 +1,67 @@
+use crate::sensors::r305_fingerprint_sensor::error::FingerprintError;
+
+use rppal::gpio::{Gpio, InputPin, Level, Trigger};
+use std::time::Duration;
+
+// the sensor's touch/WAKEUP output wired to a GPIO pin
+pub struct TouchPin {
+    pin: InputPin,
+    active: Level,
+}
+
+impl TouchPin {
+    // active is the level the sensor drives while a finger is on it (high on the R503)
+    pub fn new(pin: u8, active: Level) -> Result<Self, FingerprintError> {
+        let pin = Gpio::new()?.get(pin)?;
+        let pin = match active {
+            Level::High => pin.into_input_pulldown(),
+            Level::Low => pin.into_input_pullup(),
+        };
+
+        Ok(TouchPin { pin, active })
+    }
+
+    pub fn is_touched(&self) -> bool {
+        self.pin.read() == self.active
+    }
+
+    // blocks on an edge interrupt until a finger is placed, false on timeout
+    pub fn wait_for_finger(&mut self, timeout: Option<Duration>) -> Result<bool, FingerprintError> {
+        let trigger = match self.active {
+            Level::High => Trigger::RisingEdge,
+            Level::Low => Trigger::FallingEdge,
+        };
+        self.wait_for(true, trigger, timeout)
+    }
+
+    // blocks on an edge interrupt until the finger is lifted, false on timeout
+    pub fn wait_for_release(&mut self, timeout: Option<Duration>) -> Result<bool, FingerprintError> {
+        let trigger = match self.active {
+            Level::High => Trigger::FallingEdge,
+            Level::Low => Trigger::RisingEdge,
+        };
+        self.wait_for(false, trigger, timeout)
+    }
+
+    fn wait_for(
+        &mut self,
+        touched: bool,
+        trigger: Trigger,
+        timeout: Option<Duration>,
+    ) -> Result<bool, FingerprintError> {
+        self.pin.set_interrupt(trigger, Some(Duration::from_millis(5)))?;
+
+        // the edge may already have happened before the interrupt was armed
+        let result = if self.is_touched() == touched {
+            Ok(true)
+        } else {
+            self.pin
+                .poll_interrupt(true, timeout)
+                .map(|event| event.is_some())
+                .map_err(FingerprintError::from)
+        };
+
+        self.pin.clear_interrupt()?;
+        result
+    }
+}
@@ -0,0 +1,509 @@
+use crate::sensors::r305_fingerprint_sensor::protocol::{
+    ACK_PACKET, COMMAND_PACKET, PACKET_START_CODE_1, PACKET_START_CODE_2,
+};
+use crate::sensors::r305_fingerprint_sensor::transport::Transport;
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+const TRACE_HEADER: &str = "# attendpi packet trace v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Tx,
+    Rx,
+}
+
+impl Direction {
+    fn tag(self) -> &'static str {
+        match self {
+            Direction::Tx => "TX",
+            Direction::Rx => "RX",
+        }
+    }
+}
+
+// one line of a trace file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub micros: u128,
+    pub direction: Direction,
+    pub bytes: Vec<u8>,
+}
+
+// splits a byte stream into whole packets using the length field in the header
+#[derive(Default)]
+pub(crate) struct PacketFramer {
+    buffer: Vec<u8>,
+}
+
+impl PacketFramer {
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(bytes);
+        let mut packets = Vec::new();
+
+        loop {
+            // junk before a start code is kept as its own record so nothing is lost
+            let start = self
+                .buffer
+                .windows(2)
+                .position(|w| w == [PACKET_START_CODE_1, PACKET_START_CODE_2]);
+            match start {
+                Some(0) => {}
+                Some(idx) => {
+                    packets.push(self.buffer.drain(..idx).collect());
+                    continue;
+                }
+                None => break,
+            }
+
+            if self.buffer.len() < 9 {
+                break;
+            }
+
+            let length = 9 + u16::from_be_bytes([self.buffer[7], self.buffer[8]]) as usize;
+            if self.buffer.len() < length {
+                break;
+            }
+
+            packets.push(self.buffer.drain(..length).collect());
+        }
+
+        packets
+    }
+}
+
+// wraps a transport and records every packet going through it to a trace file
+pub struct TracingTransport {
+    inner: Box<dyn Transport>,
+    file: BufWriter<File>,
+    started: Instant,
+    tx: PacketFramer,
+    rx: PacketFramer,
+}
+
+impl TracingTransport {
+    pub fn new(inner: Box<dyn Transport>, path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let unix_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        writeln!(file, "{}", TRACE_HEADER)?;
+        writeln!(file, "# started at unix time {}", unix_time)?;
+        file.flush()?;
+
+        Ok(TracingTransport {
+            inner,
+            file,
+            started: Instant::now(),
+            tx: PacketFramer::default(),
+            rx: PacketFramer::default(),
+        })
+    }
+
+    fn record(&mut self, direction: Direction, bytes: &[u8]) -> io::Result<()> {
+        let packets = match direction {
+            Direction::Tx => self.tx.push(bytes),
+            Direction::Rx => self.rx.push(bytes),
+        };
+
+        for packet in packets {
+            writeln!(
+                self.file,
+                "{} {} {}",
+                self.started.elapsed().as_micros(),
+                direction.tag(),
+                to_hex(&packet)
+            )?;
+        }
+        // flushed every time so a trace survives the process being killed mid hang
+        self.file.flush()
+    }
+
+    fn record_error(&mut self, error: &io::Error) -> io::Result<()> {
+        writeln!(self.file, "# {} RX error: {}", self.started.elapsed().as_micros(), error)?;
+        self.file.flush()
+    }
+}
+
+impl Read for TracingTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.inner.read(buf) {
+            Ok(n) => {
+                self.record(Direction::Rx, &buf[..n])?;
+                Ok(n)
+            }
+            Err(e) => {
+                self.record_error(&e)?;
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Write for TracingTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.record(Direction::Tx, &buf[..n])?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// plays a recorded trace back as if it was the sensor
+pub struct ReplayTransport {
+    records: VecDeque<TraceRecord>,
+    pending: VecDeque<u8>,
+    tx: PacketFramer,
+}
+
+impl ReplayTransport {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let records = read_trace(path)?.into();
+
+        let mut replay = ReplayTransport {
+            records,
+            pending: VecDeque::new(),
+            tx: PacketFramer::default(),
+        };
+        // anything the sensor sent before the first command
+        replay.queue_responses();
+        Ok(replay)
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.records.len()
+    }
+
+    fn queue_responses(&mut self) {
+        while let Some(record) = self.records.front() {
+            if record.direction != Direction::Rx {
+                break;
+            }
+            let record = self.records.pop_front().unwrap();
+            self.pending.extend(record.bytes);
+        }
+    }
+}
+
+impl Read for ReplayTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // same thing a real port does when the sensor stays quiet
+        if self.pending.is_empty() {
+            return Err(io::Error::new(ErrorKind::TimedOut, "No more recorded responses"));
+        }
+
+        let n = buf.len().min(self.pending.len());
+        for (slot, byte) in buf.iter_mut().zip(self.pending.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for ReplayTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for packet in self.tx.push(buf) {
+            match self.records.pop_front() {
+                Some(record) if record.direction == Direction::Tx && record.bytes == packet => {
+                    self.queue_responses();
+                }
+                Some(record) => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "Replay diverged at {}us: expected {} {}, got TX {}",
+                            record.micros,
+                            record.direction.tag(),
+                            to_hex(&record.bytes),
+                            to_hex(&packet)
+                        ),
+                    ));
+                }
+                None => {
+                    return Err(io::Error::new(ErrorKind::UnexpectedEof, "Trace has no more packets"));
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// loads every packet record of a trace file, comments are skipped
+pub fn read_trace(path: impl AsRef<Path>) -> io::Result<Vec<TraceRecord>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        records.push(parse_record(line).ok_or_else(|| {
+            io::Error::new(ErrorKind::InvalidData, format!("Bad trace line {}: {}", line_no + 1, line))
+        })?);
+    }
+
+    Ok(records)
+}
+
+// how long the sensor took to ack one kind of instruction over a trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandLatency {
+    pub instruction: u8,
+    pub count: usize,
+    pub total_micros: u128,
+    pub max_micros: u128,
+}
+
+impl CommandLatency {
+    pub fn average_micros(&self) -> u128 {
+        self.total_micros / self.count.max(1) as u128
+    }
+}
+
+// time from each command packet to the first ack after it, grouped by instruction code
+pub fn command_latencies(records: &[TraceRecord]) -> Vec<CommandLatency> {
+    let mut latencies: Vec<CommandLatency> = Vec::new();
+
+    for (i, record) in records.iter().enumerate() {
+        if record.direction != Direction::Tx || record.bytes.get(6) != Some(&COMMAND_PACKET) {
+            continue;
+        }
+        let Some(&instruction) = record.bytes.get(9) else {
+            continue;
+        };
+        let ack = records[i + 1..]
+            .iter()
+            .take_while(|r| r.direction == Direction::Rx)
+            .find(|r| r.bytes.get(6) == Some(&ACK_PACKET));
+        let Some(ack) = ack else {
+            continue;
+        };
+
+        let elapsed = ack.micros.saturating_sub(record.micros);
+        match latencies.iter_mut().find(|l| l.instruction == instruction) {
+            Some(latency) => {
+                latency.count += 1;
+                latency.total_micros += elapsed;
+                latency.max_micros = latency.max_micros.max(elapsed);
+            }
+            None => latencies.push(CommandLatency {
+                instruction,
+                count: 1,
+                total_micros: elapsed,
+                max_micros: elapsed,
+            }),
+        }
+    }
+
+    latencies
+}
+
+fn parse_record(line: &str) -> Option<TraceRecord> {
+    let mut parts = line.split_whitespace();
+    let micros = parts.next()?.parse().ok()?;
+    let direction = match parts.next()? {
+        "TX" => Direction::Tx,
+        "RX" => Direction::Rx,
+        _ => return None,
+    };
+    let bytes = from_hex(parts.next()?)?;
+
+    Some(TraceRecord { micros, direction, bytes })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::r305_fingerprint_sensor::emulator::{EmulatedModel, EmulatedSensor};
+    use crate::sensors::r305_fingerprint_sensor::error::FingerprintError;
+    use crate::sensors::r305_fingerprint_sensor::lib::FingerprintSensor;
+    use std::path::PathBuf;
+
+    // a whole packet to the default address, checksum included
+    fn packet(packet_type: u8, payload: &[u8]) -> Vec<u8> {
+        let length = (payload.len() + 2) as u16;
+        let mut bytes = vec![PACKET_START_CODE_1, PACKET_START_CODE_2, 0xFF, 0xFF, 0xFF, 0xFF, packet_type];
+        bytes.extend_from_slice(&length.to_be_bytes());
+        bytes.extend_from_slice(payload);
+        let sum = bytes[6..].iter().fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+        bytes.extend_from_slice(&sum.to_be_bytes());
+        bytes
+    }
+
+    fn record(micros: u128, direction: Direction, bytes: Vec<u8>) -> TraceRecord {
+        TraceRecord { micros, direction, bytes }
+    }
+
+    fn trace_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("attendpi-{}-{}.trace", name, std::process::id()))
+    }
+
+    fn write_trace(path: &Path, records: &[TraceRecord]) {
+        let mut text = format!("{}\n", TRACE_HEADER);
+        for record in records {
+            text += &format!("{} {} {}\n", record.micros, record.direction.tag(), to_hex(&record.bytes));
+        }
+        std::fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn framer_waits_for_packets_split_across_reads() {
+        let command = packet(COMMAND_PACKET, &[0x1D]);
+        let mut framer = PacketFramer::default();
+
+        // header cut before the length field, then the body cut before the checksum
+        assert!(framer.push(&command[..5]).is_empty());
+        assert!(framer.push(&command[5..10]).is_empty());
+        assert_eq!(framer.push(&command[10..]), [command]);
+    }
+
+    #[test]
+    fn framer_splits_several_packets_in_one_read() {
+        let first = packet(COMMAND_PACKET, &[0x1D]);
+        let second = packet(ACK_PACKET, &[0x00, 0x00, 0x05]);
+        let bytes = [first.clone(), second.clone(), second[..3].to_vec()].concat();
+
+        let mut framer = PacketFramer::default();
+        assert_eq!(framer.push(&bytes), [first, second.clone()]);
+        // the start of a third packet is held back until the rest of it arrives
+        assert!(framer.push(&[]).is_empty());
+        assert_eq!(framer.push(&second[3..]), [second]);
+    }
+
+    #[test]
+    fn framer_keeps_junk_before_a_start_code() {
+        let command = packet(COMMAND_PACKET, &[0x1D]);
+        let mut framer = PacketFramer::default();
+        assert_eq!(framer.push(&[[0x00, 0x55].as_slice(), &command].concat()), [vec![0x00, 0x55], command]);
+    }
+
+    #[test]
+    fn recorded_session_replays() {
+        let path = trace_path("replay");
+        let emulator = {
+            let mut emulator = EmulatedSensor::new(EmulatedModel::R305, 0xFFFFFFFF, 0, 1000);
+            emulator.fill_library(3);
+            emulator
+        };
+        let tracing = TracingTransport::new(Box::new(emulator), &path).unwrap();
+        let mut sensor = FingerprintSensor::with_transport(Box::new(tracing), 0xFFFFFFFF, 0).unwrap();
+        assert_eq!(sensor.template_count().unwrap(), 3);
+        drop(sensor);
+
+        let replay = ReplayTransport::open(&path).unwrap();
+        let mut sensor = FingerprintSensor::with_transport(Box::new(replay), 0xFFFFFFFF, 0).unwrap();
+        let count = sensor.template_count();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(count.unwrap(), 3);
+    }
+
+    #[test]
+    fn replay_reports_where_it_diverged() {
+        let path = trace_path("diverged");
+        let command = packet(COMMAND_PACKET, &[0x1D]);
+        let reply = packet(ACK_PACKET, &[0x00, 0x00, 0x05]);
+        write_trace(&path, &[record(10, Direction::Tx, command.clone()), record(250, Direction::Rx, reply.clone())]);
+        let mut replay = ReplayTransport::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let error = replay.write(&packet(COMMAND_PACKET, &[0x1F, 0x00])).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("Replay diverged at 10us: expected TX"), "{}", error);
+
+        // the sensor sees it as a plain serial error, not something worth retrying
+        let error = FingerprintError::from_io("TemplateNum", error, &[]);
+        assert!(matches!(error, FingerprintError::Serial(_)));
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn replay_answers_matching_commands_then_runs_dry() {
+        let path = trace_path("dry");
+        let command = packet(COMMAND_PACKET, &[0x1D]);
+        let reply = packet(ACK_PACKET, &[0x00, 0x00, 0x05]);
+        write_trace(&path, &[record(10, Direction::Tx, command.clone()), record(250, Direction::Rx, reply.clone())]);
+        let mut replay = ReplayTransport::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        replay.write_all(&command).unwrap();
+        let mut received = vec![0; reply.len()];
+        replay.read_exact(&mut received).unwrap();
+        assert_eq!(received, reply);
+        assert_eq!(replay.remaining(), 0);
+
+        assert_eq!(replay.read(&mut received).unwrap_err().kind(), ErrorKind::TimedOut);
+        assert_eq!(replay.write(&command).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn latencies_are_grouped_by_instruction() {
+        let ack = || packet(ACK_PACKET, &[0x00]);
+        let records = [
+            record(0, Direction::Tx, packet(COMMAND_PACKET, &[0x1D])),
+            record(100, Direction::Rx, ack()),
+            record(1_000, Direction::Tx, packet(COMMAND_PACKET, &[0x01])),
+            record(1_500, Direction::Rx, ack()),
+            record(2_000, Direction::Tx, packet(COMMAND_PACKET, &[0x1D])),
+            // junk before the ack doesn't end the wait
+            record(2_100, Direction::Rx, vec![0x00]),
+            record(2_300, Direction::Rx, ack()),
+            // never answered, so left out
+            record(3_000, Direction::Tx, packet(COMMAND_PACKET, &[0x1D])),
+            record(4_000, Direction::Tx, packet(COMMAND_PACKET, &[0x01])),
+            record(4_200, Direction::Rx, ack()),
+        ];
+
+        let latencies = command_latencies(&records);
+        assert_eq!(
+            latencies,
+            [
+                CommandLatency { instruction: 0x1D, count: 2, total_micros: 400, max_micros: 300 },
+                CommandLatency { instruction: 0x01, count: 2, total_micros: 700, max_micros: 500 },
+            ]
+        );
+        assert_eq!(latencies[0].average_micros(), 200);
+        assert_eq!(latencies[1].average_micros(), 350);
+    }
+
+    #[test]
+    fn trace_files_round_trip_and_reject_bad_lines() {
+        let path = trace_path("lines");
+        let records = [record(5, Direction::Tx, packet(COMMAND_PACKET, &[0x1D])), record(9, Direction::Rx, vec![0xAB])];
+        write_trace(&path, &records);
+        assert_eq!(read_trace(&path).unwrap(), records);
+
+        std::fs::write(&path, format!("{}\n5 TX EF01\n6 XX EF01\n", TRACE_HEADER)).unwrap();
+        let error = read_trace(&path).unwrap_err();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 3"), "{}", error);
+    }
+}
@@ -0,0 +1,77 @@
+#[cfg(feature = "serial-sensor")]
+use crate::sensors::r305_fingerprint_sensor::error::FingerprintError;
+
+#[cfg(feature = "serial-sensor")]
+use serialport::{self, DataBits, ErrorKind, FlowControl, Parity, StopBits};
+use std::io::{Read, Write};
+use std::time::Duration;
+
+// anything the sensor can talk through: a serial port, a trace recorder or a replay
+pub trait Transport: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Transport for T {}
+
+// opens the first available serial port with the sensor's UART settings
+#[cfg(feature = "serial-sensor")]
+pub fn open_first_port(baud_rate: u32) -> Result<Box<dyn Transport>, FingerprintError> {
+    let ports = serialport::available_ports().map_err(|_| FingerprintError::NoFingerprintSensors)?;
+    let port_name = if ports.is_empty() {
+        return Err(FingerprintError::NoFingerprintSensors);
+    } else {
+        ports.first().unwrap().port_name.clone()
+    };
+
+    open_port(&port_name, baud_rate)
+}
+
+// opens a specific serial port, for setups with more than one sensor
+#[cfg(feature = "serial-sensor")]
+pub fn open_port(port_name: &str, baud_rate: u32) -> Result<Box<dyn Transport>, FingerprintError> {
+    if !(9600..=57600).contains(&baud_rate) {
+        let error = serialport::Error::new(ErrorKind::InvalidInput, "Baud rate must be between 9600 and 57600");
+        return Err(FingerprintError::Serial(error.into()));
+    }
+
+    let port = serialport::new(port_name, baud_rate)
+        .data_bits(DataBits::Eight)
+        .flow_control(FlowControl::None)
+        .parity(Parity::None)
+        .stop_bits(StopBits::One)
+        .timeout(Duration::from_secs(2))
+        .open()
+        .map_err(|_| FingerprintError::NoFingerprintSensors)?;
+
+    Ok(Box::new(port))
+}
+
+// lets the no_std driver core talk through any std transport
+pub struct StdSerial(pub Box<dyn Transport>);
+
+impl embedded_io::ErrorType for StdSerial {
+    type Error = std::io::Error;
+}
+
+impl embedded_io::Read for StdSerial {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.0.read(buf)
+    }
+}
+
+impl embedded_io::Write for StdSerial {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush()
+    }
+}
+
+// std sleep for the driver core's retry delays
+pub struct StdDelay;
+
+impl embedded_hal::delay::DelayNs for StdDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        std::thread::sleep(Duration::from_nanos(ns as u64));
+    }
+}
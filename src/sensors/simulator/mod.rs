@@ -0,0 +1,106 @@
+use crate::sensors::display::error::DisplayError;
+use crate::sensors::display::layout::Layout;
+use crate::sensors::display::status::{clock, Status};
+use crate::sensors::display::Display;
+
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+// same layout as the 4x4 matrix keypad
+const KEYS: &str = "123A456B789C*0#D";
+
+// keypad stand-in, every character typed on stdin (then enter) is one key press
+pub struct ConsoleKeypad {
+    keys: Receiver<char>,
+}
+
+impl ConsoleKeypad {
+    pub fn new() -> Self {
+        let (sender, keys) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                for key in line.chars().map(|c| c.to_ascii_uppercase()).filter(|c| KEYS.contains(*c)) {
+                    if sender.send(key).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        let keypad = ConsoleKeypad { keys };
+        keypad.log("Console keypad ready, type keys and press enter", false);
+        keypad
+    }
+
+    // waits about as long as one scan of the real keypad
+    pub fn read_key(&mut self) -> Option<char> {
+        let key = self.keys.recv_timeout(Duration::from_millis(200)).ok()?;
+        self.log(&format!("Key pressed: {}", key), false);
+        Some(key)
+    }
+
+    fn log(&self, message: &str, warning: bool) {
+        println!("{} Keypad: {}", if warning { "[WARNING]" } else { "[INFO]" }, message);
+    }
+}
+
+impl Default for ConsoleKeypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// display stand-in that prints each screen to stdout
+pub struct ConsoleDisplay;
+
+impl ConsoleDisplay {
+    pub fn new() -> Result<Self, io::Error> {
+        let display = ConsoleDisplay;
+        display.log("Console display ready", false);
+        Ok(display)
+    }
+
+    fn log(&self, message: &str, warning: bool) {
+        println!("{} Display: {}", if warning { "[WARNING]" } else { "[INFO]" }, message);
+    }
+}
+
+impl Display for ConsoleDisplay {
+    // whole lines are printed, there is nothing to scroll
+    fn render(&mut self, layout: &Layout, tick: u32) -> Result<bool, DisplayError> {
+        if tick > 0 {
+            return Ok(false);
+        }
+        // the selected menu item is marked with >
+        let lines: Vec<String> = layout
+            .lines()
+            .iter()
+            .enumerate()
+            .map(|(index, line)| match layout.selected() == Some(index) {
+                true => format!("> {}", line.text),
+                false => line.text.clone(),
+            })
+            .collect();
+        self.log(&format!("| {} |", lines.join(" | ")), false);
+        Ok(false)
+    }
+
+    // there is no bar to keep on screen, changes are printed instead
+    fn set_status(&mut self, status: &Status) -> Result<(), DisplayError> {
+        self.log(&format!("Status {}: sensor {:?}, network {:?}", clock(), status.sensor, status.network), false);
+        Ok(())
+    }
+
+    fn set_dimmed(&mut self, dimmed: bool) -> Result<(), DisplayError> {
+        self.log(if dimmed { "Dimmed the screen" } else { "Brightened the screen" }, false);
+        Ok(())
+    }
+
+    fn cleanup(&mut self) -> Result<(), DisplayError> {
+        self.log("Cleaned up the screen", false);
+        Ok(())
+    }
+}
@@ -1,70 +1,177 @@
-use embedded_graphics::{
-    mono_font::{ascii::FONT_6X10, MonoTextStyleBuilder},
-    pixelcolor::BinaryColor,
-    prelude::*,
-    text::{Text, TextStyleBuilder},
-};
-use linux_embedded_hal::I2cdev;
-use ssd1306::mode::BufferedGraphicsMode;
-use ssd1306::{prelude::*, I2CDisplayInterface, Ssd1306};
-use std::io::Error;
+use crate::sensors::display::error::DisplayError;
+use crate::sensors::display::framebuffer::FrameBuffer;
+use crate::sensors::display::layout::Layout;
+use crate::sensors::display::status::{draw_screen, Status};
+use crate::sensors::display::Display;
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+use embedded_hal::i2c::I2c;
+use linux_embedded_hal::{I2CError, I2cdev};
+use std::io;
+
+// I2C control bytes: the rest of the write is commands or display RAM data
+const CONTROL_COMMAND: u8 = 0x00;
+const CONTROL_DATA: u8 = 0x40;
+
+// what the controller drives, the panel is wired to a window of it
+const COLUMNS: u32 = 132;
+const ROWS: u32 = 64;
+
+// contrast while in use and while dimmed by the screensaver
+const CONTRAST: u8 = 0xFF;
+const DIMMED_CONTRAST: u8 = 0x10;
+
+// where the panel is and how it is wired to the controller
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ssd1305Config {
+    pub bus: String,
+    pub address: u8,
+    pub width: u32,
+    pub height: u32,
+    // first driver column the panel is wired to, the SSD1305 drives 132 columns
+    pub column_offset: u8,
+    // first COM row the panel is wired to
+    pub row_offset: u8,
+    // alternative (interleaved) COM pin layout, sequential wiring shows every other row
+    pub alternative_com_pins: bool,
+}
+
+impl Default for Ssd1305Config {
+    // the common 128x32 SSD1305 breakout
+    fn default() -> Self {
+        Ssd1305Config {
+            bus: "/dev/i2c-1".to_string(),
+            address: 0x3C,
+            width: 128,
+            height: 32,
+            column_offset: 4,
+            row_offset: 0,
+            alternative_com_pins: true,
+        }
+    }
+}
+
+impl Ssd1305Config {
+    // the panel has to fit the controller's RAM and be whole pages of 8 rows, otherwise the
+    // multiplex ratio and column addresses sent at init wrap around
+    pub fn validate(&self) -> io::Result<()> {
+        let invalid = |message: String| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+
+        if self.width == 0 || self.width.saturating_add(self.column_offset as u32) > COLUMNS {
+            return invalid(format!(
+                "Panel width {} at column offset {} doesn't fit the controller's {} columns",
+                self.width, self.column_offset, COLUMNS
+            ));
+        }
+        if self.height == 0 || !self.height.is_multiple_of(8) || self.height > ROWS {
+            return invalid(format!("Panel height {} isn't a multiple of 8 up to {}", self.height, ROWS));
+        }
+        if self.row_offset as u32 >= ROWS {
+            return invalid(format!("Row offset {} is past the controller's {} rows", self.row_offset, ROWS));
+        }
+        Ok(())
+    }
+}
 
 pub struct SSD1305Display {
-    device:
-        Ssd1306<I2CInterface<I2cdev>, DisplaySize128x64, BufferedGraphicsMode<DisplaySize128x64>>,
+    i2c: I2cdev,
+    config: Ssd1305Config,
+    frame: FrameBuffer,
+    status: Option<Status>,
+    // a write failed, the panel may have lost power so it is initialised again before the next frame
+    reinit: bool,
+    dimmed: bool,
 }
 
 impl SSD1305Display {
-    pub fn new() -> Result<Self, Error> {
-        let i2c = I2cdev::new("/dev/i2c-1")?;
-
-        let interface = I2CDisplayInterface::new(i2c);
+    pub fn new() -> Result<Self, DisplayError> {
+        Self::with_config(Ssd1305Config::default())
+    }
 
-        let connection = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
-            .into_buffered_graphics_mode();
-        let display = SSD1305Display { device: connection };
+    // initialises the panel once, frames after this are only flushed
+    pub fn with_config(config: Ssd1305Config) -> Result<Self, DisplayError> {
+        config.validate()?;
+        let i2c = I2cdev::new(&config.bus).map_err(io::Error::from)?;
+        let frame = FrameBuffer::with_size(config.width, config.height);
+        let mut display = SSD1305Display { i2c, config, frame, status: None, reinit: false, dimmed: false };
 
-        display.log("Display Initialized", false);
+        display.init()?;
+        display.log(
+            &format!(
+                "Display Initialized ({}x{}, column offset {})",
+                display.config.width, display.config.height, display.config.column_offset
+            ),
+            false,
+        );
         Ok(display)
     }
 
-    pub fn draw(&mut self, data: Vec<&str>) {
-        self.device.init().unwrap();
-        self.device.set_brightness(Brightness::BRIGHTEST).unwrap();
-        self.device.clear(BinaryColor::Off).unwrap();
+    // SSD1305 power-on sequence, sized from the config instead of assuming 128x64
+    fn init(&mut self) -> Result<(), I2CError> {
+        let com_pins = if self.config.alternative_com_pins { 0x12 } else { 0x02 };
+        let multiplex = (self.config.height - 1) as u8;
 
-        let text_style = MonoTextStyleBuilder::new()
-            .font(&FONT_6X10)
-            .text_color(BinaryColor::On)
-            .build();
+        self.command(&[0xAE])?; // display off
+        self.command(&[0xD5, 0xF0])?; // clock divide ratio / oscillator frequency
+        self.command(&[0xA8, multiplex])?; // multiplex ratio: one COM per panel row
+        self.command(&[0xD3, self.config.row_offset])?; // display offset
+        self.command(&[0x40])?; // start line 0
+        self.command(&[0xAD, 0x8E])?; // master configuration: external VCC
+        self.command(&[0xD8, 0x05])?; // area colour off, low power mode
+        self.command(&[0xA1])?; // segment remap, column 131 drives SEG0
+        self.command(&[0xC8])?; // COM scan from the bottom up
+        self.command(&[0xDA, com_pins])?; // COM pin configuration
+        self.command(&[0x81, self.contrast()])?; // contrast
+        self.command(&[0x82, 0x80])?; // area brightness
+        self.command(&[0xD9, 0xF1])?; // pre-charge period
+        self.command(&[0xDB, 0x40])?; // VCOMH deselect level
+        self.command(&[0x91, 0x3F, 0x3F, 0x3F, 0x3F])?; // look up table
+        self.command(&[0xA4])?; // show display RAM
+        self.command(&[0xA6])?; // not inverted
+        self.command(&[0xAF]) // display on
+    }
 
-        let mut current_display_line = 20;
-        let line_height = 10;
+    // sends the frame page by page, 8 rows per byte, starting at the panel's column offset
+    fn flush(&mut self) -> Result<(), I2CError> {
+        let column = self.config.column_offset;
 
-        for item in data.iter().take(3) {
-            let y_in_buffer = current_display_line * 2;
+        for page in 0..self.config.height / 8 {
+            self.command(&[0xB0 | page as u8, column & 0x0F, 0x10 | (column >> 4)])?;
 
-            Text::with_text_style(
-                item.to_uppercase().as_str(),
-                Point::new(5, y_in_buffer),
-                text_style,
-                TextStyleBuilder::new().build(),
-            )
-                .draw(&mut self.device)
-                .unwrap();
+            let mut data = vec![CONTROL_DATA];
+            data.extend((0..self.config.width).map(|x| {
+                (0..8).fold(0u8, |byte, bit| {
+                    byte | ((self.frame.pixel(x, page * 8 + bit) as u8) << bit)
+                })
+            }));
+            self.i2c.write(self.config.address, &data)?;
+        }
+        Ok(())
+    }
 
-            current_display_line += line_height / 2;
+    fn contrast(&self) -> u8 {
+        if self.dimmed { DIMMED_CONTRAST } else { CONTRAST }
+    }
 
-            self.log(item, false);
+    // flushes the frame, initialising the panel first when the last write failed
+    fn send_frame(&mut self) -> Result<(), DisplayError> {
+        if self.reinit {
+            self.log("Reinitialising after a bus error", true);
+            self.init()?;
+            self.reinit = false;
         }
 
-        self.device.flush().unwrap();
+        if let Err(e) = self.flush() {
+            self.reinit = true;
+            return Err(e.into());
+        }
+        Ok(())
     }
 
-    pub fn cleanup(&mut self) {
-        self.device.clear(BinaryColor::Off).unwrap();
-        self.device.flush().unwrap();
-        self.log("Cleaned up the screen", false);
+    fn command(&mut self, bytes: &[u8]) -> Result<(), I2CError> {
+        let mut data = vec![CONTROL_COMMAND];
+        data.extend_from_slice(bytes);
+        self.i2c.write(self.config.address, &data)
     }
 
     fn log(&self, message: &str, warning: bool) {
@@ -75,3 +182,39 @@ impl SSD1305Display {
         );
     }
 }
+
+impl Display for SSD1305Display {
+    fn render(&mut self, layout: &Layout, tick: u32) -> Result<bool, DisplayError> {
+        let Ok(animated) = draw_screen(&mut self.frame, layout, self.status.as_ref(), tick);
+
+        if tick == 0 {
+            for line in layout.lines() {
+                self.log(&line.text, false);
+            }
+        }
+
+        self.send_frame()?;
+        Ok(animated)
+    }
+
+    fn set_status(&mut self, status: &Status) -> Result<(), DisplayError> {
+        self.status = Some(*status);
+        Ok(())
+    }
+
+    fn set_dimmed(&mut self, dimmed: bool) -> Result<(), DisplayError> {
+        self.dimmed = dimmed;
+        if let Err(e) = self.command(&[0x81, self.contrast()]) {
+            self.reinit = true;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    fn cleanup(&mut self) -> Result<(), DisplayError> {
+        let Ok(()) = self.frame.clear(BinaryColor::Off);
+        self.send_frame()?;
+        self.log("Cleaned up the screen", false);
+        Ok(())
+    }
+}
@@ -1,84 +1,215 @@
-use crate::sensors::keypad::Keypad;
-use crate::sensors::r305_fingerprint_sensor::lib::FingerprintSensor;
-use crate::sensors::ssd1305_display::SSD1305Display;
-use std::thread::sleep;
-use std::time::Duration;
+use crate::Keypad;
+use attendpi::network::network_state;
+use attendpi::registry::UserRegistry;
+use attendpi::sensors::display::error::DisplayError;
+use attendpi::sensors::display::indicator::FINGER;
+use attendpi::sensors::display::layout::Layout;
+use attendpi::sensors::display::menu::Menu;
+use attendpi::sensors::display::renderer::{Renderer, Waker};
+use attendpi::sensors::display::status::{SensorState, Status};
+use attendpi::sensors::display::Display;
+use attendpi::sensors::r305_fingerprint_sensor::aura::{AuraColour, AuraLedConfig};
+use attendpi::sensors::r305_fingerprint_sensor::error::FingerprintError;
+use attendpi::sensors::r305_fingerprint_sensor::lib::{EnrollProgress, EnrollStage, FingerprintSensor};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, sleep};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // test fingerprint, keypad and display
 pub fn test(
     fingerprint_sensor: &mut FingerprintSensor,
-    display: &mut SSD1305Display,
+    display: &mut Renderer,
     keypad: &mut Keypad,
-) {
-    let mut index = 1;
+    registry: &mut UserRegistry,
+) -> Result<(), DisplayError> {
+    // a finger wakes the screen as soon as it is on the sensor, not once it's been searched
+    let waker = display.waker();
+    fingerprint_sensor.set_on_finger({
+        let waker = waker.clone();
+        move || {
+            let _ = waker.wake();
+        }
+    });
+
+    // the display starts out showing a connected sensor
+    let mut status = Status::default();
+    show_network_state(display, &mut status)?;
+    let capacity = fingerprint_sensor.parameters().storage_capacity;
     let mut exit_flag = false;
 
-    loop {
+    // paged templates are managed on the host, there is no slot to enroll into
+    while !fingerprint_sensor.is_paged() {
         if exit_flag {
             exit_flag = false;
             break;
         }
 
-        display.draw(vec!["Enrolling", &format!("Fingerprint {}", index)]);
+        // enroll into free slots only, existing users are never overwritten
+        let index = match registry.next_free_slot(capacity) {
+            Some(index) => index,
+            None => {
+                display.draw(vec!["Sensor Full"])?;
+                sleep(Duration::from_secs(1));
+                break;
+            }
+        };
+
+        display.draw(vec!["Enrolling", &format!("Fingerprint {}", index)])?;
 
-        match fingerprint_sensor.enroll(index) {
+        // a progress screen that doesn't make it is no reason to stop enrolling, the next
+        // screen reports the display's error
+        let enrolled = fingerprint_sensor.enroll_with(index, |progress| {
+            let _ = display.show(&enroll_layout(progress));
+        });
+        show_sensor_state(display, &mut status, &enrolled)?;
+        match enrolled {
             Ok(_) => {
-                display.draw(vec!["Enrolled!"]);
+                registry.insert(index, &format!("Fingerprint {}", index));
+                if let Err(e) = registry.save() {
+                    println!("[WARNING] Could not save user registry: {}", e);
+                }
+                display.show(&Layout::new().headline("Enrolled!"))?;
                 sleep(Duration::from_secs(1));
 
-                display.draw(vec!["1: Continue", "Any: Exit"]);
-                loop {
-                    match keypad.read_key() {
-                        None => {}
-                        Some(key) => {
-                            if key == '1' {
-                                index += 1;
-                                break;
-                            } else {
-                                exit_flag = true;
-                                break;
-                            }
-                        }
-                    }
+                if !ask_continue(display, keypad)? {
+                    exit_flag = true;
                 }
             }
-            Err(_) => {
-                display.draw(vec!["Error", "Retrying"]);
+            Err(e) if e.is_retryable() => {
+                println!("[WARNING] Enroll failed: {}", e);
+                display.draw(vec!["Error", "Retrying"])?;
                 sleep(Duration::from_millis(500));
             }
+            Err(e) => {
+                println!("Error enrolling fingerprint {}", e);
+                display.draw(vec!["Sensor Error"])?;
+                sleep(Duration::from_secs(1));
+                return display.cleanup();
+            }
         }
     }
 
     loop {
         if exit_flag { break; }
 
-        display.draw(vec!["Detecting Fingerprints..."]);
-
-        match fingerprint_sensor.search() {
-            Ok(index) => {
-                if index.is_some() {
-                    display.draw(vec!["Detected Fingerprint", &format!("{}", index.unwrap())]);
-                    sleep(Duration::from_secs(1));
-                    display.draw(vec!["1: Continue", "Any: Exit"]);
-
-                    loop {
-                        match keypad.read_key() {
-                            None => {}
-                            Some(key) => {
-                                if key == '1' {
-                                    break;
-                                } else {
-                                    exit_flag = true;
-                                    break;
-                                }
-                            }
-                        }
-                    }
+        show_network_state(display, &mut status)?;
+        display.show(&Layout::new().animation(&FINGER).centred("Detecting Fingerprints..."))?;
+
+        let searched = search_waking(fingerprint_sensor, keypad, &waker);
+        show_sensor_state(display, &mut status, &searched)?;
+        match searched {
+            Ok(Some(index)) => {
+                signal(fingerprint_sensor, AuraLedConfig::on(AuraColour::Green));
+                let name = registry.name(index).map(str::to_string).unwrap_or(format!("{}", index));
+                display.show(&Layout::new().centred("Welcome").headline(name))?;
+                sleep(Duration::from_secs(1));
+                signal(fingerprint_sensor, AuraLedConfig::off());
+
+                display.show(&Layout::new().qr(receipt(index)).centred("Checked in"))?;
+                sleep(Duration::from_secs(2));
+                if !ask_continue(display, keypad)? {
+                    exit_flag = true;
                 }
             }
-            Err(_) => {}
+            Ok(None) => {
+                signal(fingerprint_sensor, AuraLedConfig::flashing(AuraColour::Red, 3));
+            }
+            // nobody put a finger on the sensor
+            Err(FingerprintError::MaxRetries) => {}
+            Err(e) if e.is_retryable() => println!("[WARNING] Search failed: {}", e),
+            Err(e) => {
+                println!("Error searching fingerprints {}", e);
+                display.draw(vec!["Sensor Error"])?;
+                sleep(Duration::from_secs(1));
+                break;
+            }
         }
     }
 
-    display.cleanup();
-}
\ No newline at end of file
+    display.cleanup()
+}
+
+// the sensor icon follows whether the sensor last answered, sent only when that changes
+fn show_sensor_state<T>(
+    display: &mut dyn Display,
+    status: &mut Status,
+    result: &Result<T, FingerprintError>,
+) -> Result<(), DisplayError> {
+    let sensor = match result {
+        Err(e) if e.is_link_error() => SensorState::Lost,
+        _ => SensorState::Connected,
+    };
+    update_status(display, status, Status { sensor, ..*status })
+}
+
+// the network icon follows the routing table, checked before each search
+fn show_network_state(display: &mut dyn Display, status: &mut Status) -> Result<(), DisplayError> {
+    update_status(display, status, Status { network: network_state(), ..*status })
+}
+
+// sends the bar only when something on it changed
+fn update_status(display: &mut dyn Display, shown: &mut Status, status: Status) -> Result<(), DisplayError> {
+    if *shown != status {
+        *shown = status;
+        display.set_status(shown)?;
+    }
+    Ok(())
+}
+
+// searches with the keypad scanned on another thread meanwhile, so a key wakes the screen
+// straight away rather than once the search gives up. Keys pressed meanwhile only wake it
+fn search_waking(
+    fingerprint_sensor: &mut FingerprintSensor,
+    keypad: &mut Keypad,
+    waker: &Waker,
+) -> Result<Option<u16>, FingerprintError> {
+    let searching = AtomicBool::new(true);
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            while searching.load(Ordering::SeqCst) {
+                if keypad.read_key().is_some() {
+                    let _ = waker.wake();
+                }
+            }
+        });
+        let searched = fingerprint_sensor.search();
+        searching.store(false, Ordering::SeqCst);
+        searched
+    })
+}
+
+// scanned as proof of a check-in: slot and unix time
+fn receipt(index: u16) -> String {
+    let unix_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    format!("attendpi:{}:{}", index, unix_time)
+}
+
+// 2/8 to pick, # to choose, * backs out and exits too
+fn ask_continue(display: &mut dyn Display, keypad: &mut Keypad) -> Result<bool, DisplayError> {
+    Ok(Menu::new(["Continue", "Exit"]).run(display, || keypad.read_key())? == Some(0))
+}
+
+// shows match/reject on the aura LED when the sensor has one
+fn signal(fingerprint_sensor: &mut FingerprintSensor, config: AuraLedConfig) {
+    if !fingerprint_sensor.capabilities().aura_led {
+        return;
+    }
+
+    if let Err(e) = fingerprint_sensor.set_aura_led(config) {
+        println!("[WARNING] Aura LED: {}", e);
+    }
+}
+// the finger animation while the sensor waits for a finger, a spinner while it works
+fn enroll_layout(progress: EnrollProgress) -> Layout {
+    let layout = match progress.stage {
+        EnrollStage::PlaceFinger => Layout::new().animation(&FINGER).centred("Place finger"),
+        EnrollStage::PlaceAgain => Layout::new().animation(&FINGER).centred("Place again"),
+        EnrollStage::Merging => Layout::new().spinner().centred("Merging"),
+        EnrollStage::Storing => Layout::new().spinner().centred("Saving"),
+        EnrollStage::Done => Layout::new().centred("Saved"),
+    };
+    layout.progress(progress.step, progress.steps)
+}